@@ -0,0 +1,269 @@
+use ndrcraft::{
+    impl_from_error,
+    voxel::{self, BlockMapping, ExportFormat, Generator, PasteOptions, Region, Schematic, World},
+    Extent3d, Offset3d,
+};
+use std::{env, fmt, fs, io, path::PathBuf, process, time};
+
+const USAGE: &str = "usage: worldgen --output <file> [--seed <u64>] \
+[--size <width>x<height>x<depth>] [--generator <flat|waves|hills>] [--tiles <count>] \
+[--textures <dir>] [--preview <png>] [--preview-scale <pixels>] [--schem <file>] \
+[--mapping <file>] [--export <obj|glb|ply file>] [--greedy]";
+
+#[derive(Debug)]
+struct Options {
+    output: PathBuf,
+    seed: u64,
+    size: Extent3d<u32>,
+    generator: Generator,
+    max_tiles: u32,
+    textures: Option<PathBuf>,
+    preview: Option<PathBuf>,
+    preview_scale: u32,
+    schem: Option<PathBuf>,
+    mapping: PathBuf,
+    export: Option<(PathBuf, ExportFormat)>,
+    greedy: bool,
+}
+
+#[derive(Debug)]
+enum Error {
+    World(voxel::WorldError),
+    File(voxel::FileError),
+    Export(voxel::ExportError),
+    Io(io::Error),
+    Image(image::ImageError),
+}
+
+impl_from_error!(voxel::WorldError, Error, World);
+impl_from_error!(voxel::FileError, Error, File);
+impl_from_error!(voxel::ExportError, Error, Export);
+impl_from_error!(io::Error, Error, Io);
+impl_from_error!(image::ImageError, Error, Image);
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::World(error) => write!(f, "world: {error:?}"),
+            Self::File(error) => write!(f, "world file: {error:?}"),
+            Self::Export(error) => write!(f, "export: {error:?}"),
+            Self::Io(error) => write!(f, "{error}"),
+            Self::Image(error) => write!(f, "preview: {error}"),
+        }
+    }
+}
+
+fn main() {
+    let options = match parse_options(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{message}\n{USAGE}");
+            process::exit(2);
+        }
+    };
+    if let Err(error) = run(&options) {
+        eprintln!("error: {error}");
+        process::exit(1);
+    }
+}
+
+fn run(options: &Options) -> Result<(), Error> {
+    // the tile count grows to fit whatever the mapping of an imported build refers to
+    let mapping = match options.schem {
+        Some(_) => Some(BlockMapping::load(&mut fs::File::open(&options.mapping)?)?),
+        None => None,
+    };
+    let max_tiles = mapping.as_ref().map_or(options.max_tiles, |mapping| {
+        options.max_tiles.max(mapping.max_tiles())
+    });
+    if max_tiles > options.max_tiles {
+        println!(
+            "raised tile count to {max_tiles} to fit {}",
+            options.mapping.display()
+        );
+    }
+
+    let start = time::Instant::now();
+    let mut world = World::new_headless(Region::centered(options.size), max_tiles);
+    world.generate(options.generator, options.seed)?;
+    let duration = start.elapsed();
+    println!(
+        "generated '{}' world (seed {}, size {}x{}x{}) in {:.3}s",
+        options.generator.name(),
+        options.seed,
+        options.size.width,
+        options.size.height,
+        options.size.depth,
+        duration.as_secs_f32()
+    );
+
+    // imported builds stand on y = 1, centred on the world
+    if let (Some(schem), Some(mapping)) = (options.schem.as_ref(), mapping.as_ref()) {
+        let import = Schematic::load_sponge(&mut fs::File::open(schem)?, mapping)?;
+        let size = import.schematic.size();
+        let origin = Offset3d::new(-(size.width as i32 / 2), 1, -(size.depth as i32 / 2));
+        let changed = world.paste(&import.schematic, origin, PasteOptions::default())?;
+        println!(
+            "pasted {} ({}x{}x{}), {changed} voxels changed",
+            schem.display(),
+            size.width,
+            size.height,
+            size.depth
+        );
+        for (name, count) in import.unmapped {
+            println!("  unmapped {name}: {count}");
+        }
+    }
+
+    let mut bytes = Vec::new();
+    world.save(&mut bytes)?;
+    fs::write(&options.output, &bytes)?;
+
+    println!(
+        "wrote {} bytes to {}",
+        bytes.len(),
+        options.output.display()
+    );
+    println!("checksum: {:016x}", checksum(&bytes));
+
+    let stats = world.stats();
+    let solid_count = stats.solid_count();
+    println!("solid voxels: {solid_count}");
+    for (tile_index, count) in stats.tile_counts.iter().enumerate() {
+        if *count > 0 {
+            let percentage = *count as f32 / solid_count as f32 * 100.0;
+            println!("  tile {tile_index}: {count} ({percentage:.1}%)");
+        }
+    }
+    println!(
+        "faces: {} ({} vertices, {} indices), memory: {} KiB",
+        stats.face_count,
+        stats.vertex_count,
+        stats.index_count,
+        stats.memory_used / 1024
+    );
+    let mismatches = world.validate();
+    if !mismatches.is_empty() {
+        println!("face mismatches: {}", mismatches.len());
+    }
+
+    // tiles without a texture fall back to a fixed colour
+    if let Some(textures) = options.textures.as_ref() {
+        for tile_index in 0..world.max_tiles() {
+            let path = textures.join(format!("voxel_{tile_index}.png"));
+            if path.exists() {
+                let texture = image::open(path)?.into_rgba8();
+                world.set_voxel_texture(tile_index, voxel::TextureLayout::Single, &texture)?;
+            }
+        }
+    }
+    if let Some(preview) = options.preview.as_ref() {
+        world
+            .render_map(world.bounds(), options.preview_scale)
+            .save(preview)?;
+        println!("wrote preview to {}", preview.display());
+    }
+    if let Some((path, format)) = options.export.as_ref() {
+        let start = time::Instant::now();
+        world.export(path, *format, world.bounds(), options.greedy)?;
+        println!(
+            "exported mesh to {} in {:.3}s",
+            path.display(),
+            start.elapsed().as_secs_f32()
+        );
+    }
+    Ok(())
+}
+
+fn parse_options(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut output = None;
+    let mut seed = 0;
+    let mut size = Extent3d::new(100, 12, 100);
+    let mut generator = Generator::default();
+    let mut max_tiles = Generator::TILE_COUNT;
+    let mut textures = None;
+    let mut preview = None;
+    let mut preview_scale = 1;
+    let mut schem = None;
+    let mut mapping = PathBuf::from("assets/schem_mapping.txt");
+    let mut export = None;
+    let mut greedy = false;
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("missing value for '{arg}'"));
+        match arg.as_str() {
+            "--output" | "-o" => output = Some(PathBuf::from(value()?)),
+            "--seed" => seed = value()?.parse().map_err(|_| "seed is invalid")?,
+            "--size" => {
+                size = parse_size(&value()?).ok_or("size is invalid")?;
+                let volume = size.width as u64 * size.height as u64 * size.depth as u64;
+                if volume > World::MAX_VOLUME {
+                    return Err(format!(
+                        "size must hold at most {} voxels",
+                        World::MAX_VOLUME
+                    ));
+                }
+            }
+            "--generator" => {
+                let name = value()?;
+                generator =
+                    Generator::from_name(&name).ok_or(format!("generator '{name}' is unknown"))?;
+            }
+            "--tiles" => {
+                max_tiles = value()?.parse().map_err(|_| "tile count is invalid")?;
+                if max_tiles < Generator::TILE_COUNT {
+                    return Err(format!(
+                        "tile count must be at least {}",
+                        Generator::TILE_COUNT
+                    ));
+                }
+            }
+            "--textures" => textures = Some(PathBuf::from(value()?)),
+            "--preview" => preview = Some(PathBuf::from(value()?)),
+            "--preview-scale" => {
+                preview_scale = value()?.parse().map_err(|_| "preview scale is invalid")?
+            }
+            "--schem" => schem = Some(PathBuf::from(value()?)),
+            "--mapping" => mapping = PathBuf::from(value()?),
+            "--export" => {
+                let path = PathBuf::from(value()?);
+                let format = path
+                    .extension()
+                    .and_then(|extension| {
+                        ExportFormat::from_extension(&extension.to_string_lossy())
+                    })
+                    .ok_or("export format is unknown, use .obj, .glb or .ply")?;
+                export = Some((path, format));
+            }
+            "--greedy" => greedy = true,
+            "--help" | "-h" => return Err(String::new()),
+            _ => return Err(format!("unknown argument '{arg}'")),
+        }
+    }
+    Ok(Options {
+        output: output.ok_or("missing '--output'")?,
+        seed,
+        size,
+        generator,
+        max_tiles,
+        textures,
+        preview,
+        preview_scale,
+        schem,
+        mapping,
+        export,
+        greedy,
+    })
+}
+
+fn parse_size(value: &str) -> Option<Extent3d<u32>> {
+    let mut parts = value.split('x').map(|part| part.parse().ok());
+    let size = Extent3d::new(parts.next()??, parts.next()??, parts.next()??);
+    (parts.next().is_none() && size.is_valid()).then_some(size)
+}
+
+// FNV-1a, stable across platforms and toolchains
+fn checksum(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xCBF2_9CE4_8422_2325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x0000_0100_0000_01B3)
+    })
+}
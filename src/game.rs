@@ -4,7 +4,7 @@ use crate::{
     types::*,
     voxel, Controller, Voxel,
 };
-use std::time;
+use std::{fs, io, path::PathBuf, time};
 use winit::{
     dpi::{PhysicalPosition, PhysicalSize},
     error::OsError,
@@ -30,33 +30,58 @@ pub enum WindowMode {
 pub struct Descriptor {
     pub window: WindowMode,
     pub vsync: bool,
+    // multisampled anti-aliasing, 1 (off), 2, 4 or 8 if the adapter supports it
+    pub sample_count: u32,
+    pub graphics: graphics::Descriptor,
 
-    pub world_size: Extent3d<u32>,
+    pub world_bounds: voxel::Region,
+    pub world_generator: voxel::Generator,
+    pub world_seed: u64,
+
+    // screenshots above 1 are rendered offscreen at this multiple of the window size
+    pub screenshot_scale: u32,
 }
 
 #[derive(Debug)]
 pub enum Error {
     CreateWindowFailed(OsError),
     Graphics(graphics::Error),
+    Renderer(mesh::RendererError),
     World(voxel::WorldError),
+    Io(io::Error),
+    Image(image::ImageError),
 }
 
 impl_from_error!(graphics::Error, Error, Graphics);
+impl_from_error!(mesh::RendererError, Error, Renderer);
 impl_from_error!(voxel::WorldError, Error, World);
+impl_from_error!(io::Error, Error, Io);
+impl_from_error!(image::ImageError, Error, Image);
 
 pub struct Game {
     settings: Descriptor,
     graphics: graphics::Context,
     world: voxel::World,
+    world_events: voxel::EventReceiver,
     controller: Controller,
 }
 
 impl Game {
     const TITLE: &'static str = "NdrCraft";
+    const TICK_INTERVAL: time::Duration = time::Duration::from_millis(100);
+    const SCREENSHOT_DIRECTORY: &'static str = "screenshots";
+    const MATERIAL: mesh::Material = mesh::Material {
+        blend: mesh::BlendMode::Opaque,
+        cull: Some(mesh::CullMode::Back),
+    };
+    const FLUID_MATERIAL: mesh::Material = mesh::Material {
+        blend: mesh::BlendMode::Translucent,
+        cull: Some(mesh::CullMode::Back),
+    };
 
     pub fn new(descriptor: Descriptor) -> Result<Self, Error> {
-        let graphics = graphics::Context::new()?;
-        let mut world = voxel::World::new(&graphics, descriptor.world_size, 3)?;
+        let graphics = graphics::Context::new(descriptor.graphics)?;
+        let mut world = voxel::World::new(&graphics, descriptor.world_bounds, 6)?;
 
         let voxel_0 = image::io::Reader::open("assets/textures/voxel_0.png")
             .unwrap()
@@ -70,39 +95,25 @@ impl Game {
             .unwrap()
             .decode()
             .unwrap();
+        let fluid_0 = image::io::Reader::open("assets/textures/fluid_0.png")
+            .unwrap()
+            .decode()
+            .unwrap();
         world.set_voxel_texture(0, voxel::TextureLayout::Single, voxel_0.as_bytes())?;
         world.set_voxel_texture(1, voxel::TextureLayout::Single, voxel_1.as_bytes())?;
         world.set_voxel_texture(2, voxel::TextureLayout::Single, voxel_2.as_bytes())?;
+        world.set_voxel_texture(3, voxel::TextureLayout::Single, fluid_0.as_bytes())?;
+        // the generators only place tiles 0 to 2, so the simulated tiles get their own indices
+        world.set_voxel_texture(4, voxel::TextureLayout::Single, voxel_0.as_bytes())?;
+        world.set_voxel_texture(5, voxel::TextureLayout::Single, voxel_1.as_bytes())?;
+        world.set_tile_behaviour(4, voxel::Falling { delay: 2 })?;
+        world.set_tile_behaviour(5, voxel::Spreading { target: 0 })?;
 
         {
             performance::ScopedTimer::new("Generating world");
-            let width = (world.size().width / 2) as i32;
-            let height = (world.size().height / 2) as i32;
-            let depth = (world.size().depth / 2) as i32;
-            for z in -width..width {
-                for y in -height..=0 {
-                    for x in -depth..depth {
-                        let threshold =
-                            (x as f32 * 0.12).sin() * 1.2 + (z as f32 * 0.05).cos() * 0.5;
-                        if y as f32 > threshold {
-                            continue;
-                        }
-                        use std::collections::hash_map::DefaultHasher;
-                        use std::hash::{Hash, Hasher};
-
-                        let tile_index = {
-                            let mut hasher = DefaultHasher::new();
-                            z.hash(&mut hasher);
-                            y.hash(&mut hasher);
-                            x.hash(&mut hasher);
-                            (hasher.finish() % 3) as u32
-                        };
-                        world.set_voxel(Offset3d::new(x, y, z), Voxel::Tile(tile_index))?;
-                    }
-                }
-            }
-            world.set_voxel(Offset3d::new(1, 4, 0), Voxel::Tile(0))?;
-            world.set_voxel(Offset3d::new(0, 5, 0), Voxel::Tile(1))?;
+            world.generate(descriptor.world_generator, descriptor.world_seed)?;
+            world.set_voxel(Offset3d::new(1, 4, 0), Voxel::Tile(4))?;
+            world.set_voxel(Offset3d::new(0, 5, 0), Voxel::Tile(5))?;
             world.set_voxel(Offset3d::new(0, 4, 1), Voxel::Tile(2))?;
             world.set_voxel(Offset3d::new(-2, -2, 0), Voxel::Void)?;
             world.set_voxel(Offset3d::new(0, -2, 0), Voxel::Void)?;
@@ -111,16 +122,22 @@ impl Game {
             if let Some(_) = world.get_voxel(void_pos) {
                 world.set_voxel(void_pos, Voxel::Void)?;
             }
+            world.set_voxel(
+                Offset3d::new(4, 4, -4),
+                Voxel::Fluid(voxel::Fluid::new_source(3)),
+            )?;
         }
         {
             performance::ScopedTimer::new("Generating world mesh");
             world.generate_mesh();
         }
+        let world_events = world.subscribe();
         let controller = Controller::new(Vector3::new(0.0, 2.0, 3.0), Deg(0.0), Deg(-30.0));
         Ok(Self {
             settings: descriptor,
             graphics,
             world,
+            world_events,
             controller,
         })
     }
@@ -158,9 +175,12 @@ impl Game {
         }
 
         // create render target
-        let mut render_target =
-            self.graphics
-                .create_render_target(&window, self.settings.vsync, true)?;
+        let mut render_target = self.graphics.create_render_target(
+            &window,
+            self.settings.vsync,
+            true,
+            self.settings.sample_count,
+        )?;
 
         // create renderers
         let mut mesh_renderer = self.graphics.create_mesh_renderer(
@@ -168,18 +188,13 @@ impl Game {
             get_projection(window.inner_size().into()),
         );
 
-        // create renderables
-        let material = mesh::Material {
-            blend: mesh::BlendMode::Opaque,
-            cull: Some(mesh::CullMode::Back),
-        };
-
         // timekeeping data (delta time, frame count)
         let mut frame_count = 0u64;
         let start = time::Instant::now();
         let mut delta_time = time::Duration::default();
         let mut last_tick = start;
         let mut last_second = start;
+        let mut tick_time = time::Duration::default();
 
         // used to update the controller
         let lateral_speed = 10.0;
@@ -189,6 +204,7 @@ impl Game {
         let mut forward_axis = input::Axis::default();
         let mut up_axis = input::Axis::default();
         let mut look_delta = Vector2::new(0.0, 0.0);
+        let mut screenshot_requested = false;
 
         event_loop.run(move |event, _, flow| match event {
             // main events
@@ -209,8 +225,22 @@ impl Game {
                 }
 
                 frame_count += 1;
+                tick_time += delta_time;
             }
             Event::MainEventsCleared => {
+                // advance the world simulation at a fixed rate
+                while tick_time >= Self::TICK_INTERVAL {
+                    tick_time -= Self::TICK_INTERVAL;
+                    if let Err(error) = self.world.tick() {
+                        log::error!("{error:?}");
+                    }
+                }
+
+                // re-mesh once for everything that changed this frame
+                if self.world_events.drain().count() > 0 {
+                    self.world.generate_mesh();
+                }
+
                 let delta_time = delta_time.as_secs_f32();
 
                 look_delta.normalize();
@@ -269,6 +299,9 @@ impl Game {
                             // close window
                             Some(VirtualKeyCode::Escape) => flow.set_exit(),
 
+                            // capture the next frame
+                            Some(VirtualKeyCode::F2) => screenshot_requested = true,
+
                             // movement
                             Some(VirtualKeyCode::A) => right_axis.negative = true,
                             Some(VirtualKeyCode::D) => right_axis.positive = true,
@@ -293,19 +326,27 @@ impl Game {
                 _ => {}
             },
             Event::RedrawRequested(window_id) if window_id == window.id() => {
-                log_on_err!(mesh_renderer.draw_mesh(
-                    Matrix4::identity(),
-                    self.world.mesh(),
-                    &[mesh::MaterialTexture {
-                        material,
-                        texture: Some(self.world.texture()),
-                    }],
-                ));
+                if screenshot_requested {
+                    screenshot_requested = false;
+                    match self.settings.screenshot_scale {
+                        0 | 1 => render_target.capture_next_frame(),
+                        scale => {
+                            let size = window.inner_size().into();
+                            log_on_err!(self
+                                .render_screenshot(size, scale, get_projection(size))
+                                .and_then(|image| save_screenshot(&image)));
+                        }
+                    }
+                }
+                log_on_err!(self.draw_world(&mut mesh_renderer));
                 log_on_err!(render_target.draw_pass(
                     Some(Color::black()),
                     Some(1.0),
                     mesh_renderer.submit()
                 ));
+                if let Some(image) = render_target.take_capture() {
+                    log_on_err!(save_screenshot(&image));
+                }
             }
 
             // mouse motion events
@@ -319,4 +360,70 @@ impl Game {
             _ => {}
         });
     }
+
+    fn draw_world(&self, renderer: &mut mesh::Renderer) -> Result<(), mesh::RendererError> {
+        let Some(world_mesh) = self.world.mesh() else { return Ok(()); };
+        renderer.draw_mesh(
+            Matrix4::identity(),
+            world_mesh,
+            &[
+                mesh::MaterialTexture {
+                    material: Self::MATERIAL,
+                    texture: self.world.texture(),
+                },
+                mesh::MaterialTexture {
+                    material: Self::FLUID_MATERIAL,
+                    texture: self.world.texture(),
+                },
+            ],
+        )
+    }
+
+    // renders the current view offscreen at `scale` times `size`, then filters it back down
+    fn render_screenshot(
+        &self,
+        size: Extent2d<u32>,
+        scale: u32,
+        projection: mesh::Projection,
+    ) -> Result<image::RgbaImage, Error> {
+        let scaled_size = Extent2d::new(size.width * scale, size.height * scale);
+        let render_target = self.graphics.create_offscreen_render_target(
+            scaled_size,
+            true,
+            self.settings.sample_count,
+        )?;
+        let mut renderer = self
+            .graphics
+            .create_mesh_renderer(render_target.target_format(), projection);
+        renderer.set_view(
+            self.controller
+                .get_transform_matrix()
+                .inverse_transform()
+                .unwrap(),
+        );
+        self.draw_world(&mut renderer)?;
+        render_target
+            .draw_pass(Some(Color::black()), Some(1.0), renderer.submit())
+            .map_err(graphics::Error::from)?;
+        let image = render_target.read_pixels().map_err(graphics::Error::from)?;
+        Ok(image::imageops::resize(
+            &image,
+            size.width,
+            size.height,
+            image::imageops::FilterType::Triangle,
+        ))
+    }
+}
+
+// writes to screenshots/<unix time in milliseconds>.png
+fn save_screenshot(image: &image::RgbaImage) -> Result<PathBuf, Error> {
+    fs::create_dir_all(Game::SCREENSHOT_DIRECTORY)?;
+    let timestamp = time::SystemTime::now()
+        .duration_since(time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    let path = PathBuf::from(Game::SCREENSHOT_DIRECTORY).join(format!("{timestamp}.png"));
+    image.save(&path)?;
+    log::info!("saved screenshot to {}", path.display());
+    Ok(path)
 }
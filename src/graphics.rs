@@ -8,13 +8,135 @@ pub use texture::Texture;
 
 use crate::{impl_from_error, Extent2d};
 use pollster::block_on;
-use std::rc::Rc;
+use std::{env, rc::Rc};
 use winit::window::Window;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
-pub(crate) struct TargetFormat {
-    pub color_format: wgpu::TextureFormat,
-    pub depth_format: Option<wgpu::TextureFormat>,
+pub enum Backend {
+    Vulkan,
+    Metal,
+    Dx12,
+    Dx11,
+    Gl,
+}
+
+impl Backend {
+    // the order backends are tried in after the preferred one
+    pub const ALL: [Self; 5] = [Self::Vulkan, Self::Metal, Self::Dx12, Self::Dx11, Self::Gl];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Vulkan => "vulkan",
+            Self::Metal => "metal",
+            Self::Dx12 => "dx12",
+            Self::Dx11 => "dx11",
+            Self::Gl => "gl",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.to_lowercase();
+        Self::ALL
+            .into_iter()
+            .find(|backend| backend.name() == name)
+            .or(match name.as_str() {
+                "vk" => Some(Self::Vulkan),
+                "mtl" => Some(Self::Metal),
+                "d3d12" => Some(Self::Dx12),
+                "d3d11" => Some(Self::Dx11),
+                "opengl" | "gles" => Some(Self::Gl),
+                _ => None,
+            })
+    }
+}
+
+impl From<Backend> for wgpu::Backends {
+    fn from(value: Backend) -> Self {
+        match value {
+            Backend::Vulkan => Self::VULKAN,
+            Backend::Metal => Self::METAL,
+            Backend::Dx12 => Self::DX12,
+            Backend::Dx11 => Self::DX11,
+            Backend::Gl => Self::GL,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum PowerPreference {
+    LowPower,
+    #[default]
+    HighPerformance,
+}
+
+impl From<PowerPreference> for wgpu::PowerPreference {
+    fn from(value: PowerPreference) -> Self {
+        match value {
+            PowerPreference::LowPower => Self::LowPower,
+            PowerPreference::HighPerformance => Self::HighPerformance,
+        }
+    }
+}
+
+/// How the adapter is picked. `WGPU_BACKEND`, `WGPU_POWER_PREF` and
+/// `WGPU_FORCE_FALLBACK_ADAPTER` override the fields when set.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Descriptor {
+    // tried first, the other backends follow in the order of `Backend::ALL`
+    pub backend: Backend,
+    pub power_preference: PowerPreference,
+    // a software adapter such as lavapipe or WARP
+    pub force_fallback_adapter: bool,
+}
+
+impl Default for Descriptor {
+    fn default() -> Self {
+        Self {
+            backend: Backend::Vulkan,
+            power_preference: PowerPreference::default(),
+            force_fallback_adapter: false,
+        }
+    }
+}
+
+impl Descriptor {
+    pub fn with_env_overrides(mut self) -> Self {
+        if let Ok(name) = env::var("WGPU_BACKEND") {
+            match Backend::from_name(&name) {
+                Some(backend) => self.backend = backend,
+                None => log::warn!("WGPU_BACKEND '{name}' is unknown"),
+            }
+        }
+        if let Ok(name) = env::var("WGPU_POWER_PREF") {
+            match name.to_lowercase().as_str() {
+                "low" => self.power_preference = PowerPreference::LowPower,
+                "high" => self.power_preference = PowerPreference::HighPerformance,
+                _ => log::warn!("WGPU_POWER_PREF '{name}' is unknown, use low or high"),
+            }
+        }
+        if let Ok(value) = env::var("WGPU_FORCE_FALLBACK_ADAPTER") {
+            self.force_fallback_adapter = matches!(value.to_lowercase().as_str(), "1" | "true");
+        }
+        self
+    }
+
+    // the preferred backend followed by the rest
+    fn backends(&self) -> impl Iterator<Item = Backend> {
+        let backend = self.backend;
+        Some(backend).into_iter().chain(
+            Backend::ALL
+                .into_iter()
+                .filter(move |other| *other != backend),
+        )
+    }
+}
+
+/// The attachment formats of a render target, renderers draw for exactly one of them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TargetFormat {
+    pub(crate) color_format: wgpu::TextureFormat,
+    pub(crate) depth_format: Option<wgpu::TextureFormat>,
+    pub(crate) sample_count: u32,
 }
 
 #[derive(Debug)]
@@ -23,6 +145,7 @@ pub(crate) enum DrawCommand<const PUSH_SIZE: usize = 128> {
     SetBindGroup {
         index: u32,
         bind_group: Rc<wgpu::BindGroup>,
+        offsets: Vec<wgpu::DynamicOffset>,
     },
     SetPushConstant {
         stages: wgpu::ShaderStages,
@@ -47,12 +170,29 @@ pub(crate) enum DrawCommand<const PUSH_SIZE: usize = 128> {
         start: u32,
         end: u32,
     },
+    // the per instance vertex buffer, bound to slot 1
+    SetInstanceBuffer {
+        buffer: Rc<wgpu::Buffer>,
+        start: u64,
+        end: u64,
+    },
+    DrawInstanced {
+        start: u32,
+        end: u32,
+        instances: (u32, u32),
+    },
+    DrawIndexedInstanced {
+        start: u32,
+        end: u32,
+        instances: (u32, u32),
+    },
 }
 
+/// Recorded draws of a renderer, replayed by [`RenderTarget::draw_pass`].
 #[derive(Debug)]
-pub(crate) struct DrawCommandList<const PUSH_SIZE: usize> {
-    pub target_format: TargetFormat,
-    pub draw_commands: Vec<DrawCommand<PUSH_SIZE>>,
+pub struct DrawCommandList<const PUSH_SIZE: usize> {
+    pub(crate) target_format: TargetFormat,
+    pub(crate) draw_commands: Vec<DrawCommand<PUSH_SIZE>>,
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -73,24 +213,42 @@ pub struct Context {
     pub(crate) device: Rc<wgpu::Device>,
     pub(crate) queue: Rc<wgpu::Queue>,
     pub(crate) default_texture: Rc<Texture>,
+    // without them, renderers pass per draw data through a uniform buffer
+    pub(crate) push_constants: bool,
 }
 
 impl Context {
-    pub(crate) fn new() -> Result<Self, Error> {
-        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
-            backends: wgpu::Backends::VULKAN,
-            dx12_shader_compiler: wgpu::Dx12Compiler::default(),
-        });
-        let adapter = block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
-            power_preference: wgpu::PowerPreference::HighPerformance,
-            force_fallback_adapter: false,
-            compatible_surface: None,
-        }))
-        .ok_or(Error::RequestAdapterFailed)?;
-        let features = wgpu::Features::PUSH_CONSTANTS;
+    pub fn new(descriptor: Descriptor) -> Result<Self, Error> {
+        let descriptor = descriptor.with_env_overrides();
+        let (instance, adapter) =
+            Self::request_adapter(&descriptor).ok_or(Error::RequestAdapterFailed)?;
+        let info = adapter.get_info();
+        log::info!(
+            "using adapter '{}' ({:?}, {:?})",
+            info.name,
+            info.backend,
+            info.device_type
+        );
+        // adapter specific format features allow sample counts other than 1 and 4
+        let features = adapter.features()
+            & (wgpu::Features::PUSH_CONSTANTS
+                | wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES);
+        let push_constants = features.contains(wgpu::Features::PUSH_CONSTANTS);
+        if !push_constants {
+            log::info!("push constants are unavailable, using uniform buffers instead");
+        }
+
+        // adapters that can't meet the default limits, like most GL ones, get the downlevel ones
+        let adapter_limits = adapter.limits();
+        let base_limits = match wgpu::Limits::default().check_limits(&adapter_limits) {
+            true => wgpu::Limits::default(),
+            false => wgpu::Limits::downlevel_defaults(),
+        };
+        // array layers bound how many tiles a world's atlas can hold
         let limits = wgpu::Limits {
-            max_push_constant_size: adapter.limits().max_push_constant_size,
-            ..Default::default()
+            max_push_constant_size: adapter_limits.max_push_constant_size,
+            max_texture_array_layers: adapter_limits.max_texture_array_layers,
+            ..base_limits.using_resolution(adapter_limits)
         };
         let (device, queue) = block_on(adapter.request_device(
             &wgpu::DeviceDescriptor {
@@ -109,6 +267,7 @@ impl Context {
             texture::Format::Rgba8Unorm,
             None,
             Some(&[0xFF, 0xFF, 0xFF, 0xFF]),
+            false,
         )?);
         Ok(Self {
             instance,
@@ -116,15 +275,51 @@ impl Context {
             device,
             queue,
             default_texture,
+            push_constants,
         })
     }
 
+    // tries every backend in order until one has an adapter
+    fn request_adapter(descriptor: &Descriptor) -> Option<(wgpu::Instance, wgpu::Adapter)> {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::all(),
+            dx12_shader_compiler: wgpu::Dx12Compiler::default(),
+        });
+        for adapter in instance.enumerate_adapters(wgpu::Backends::all()) {
+            let info = adapter.get_info();
+            log::info!(
+                "found adapter '{}' ({:?}, {:?})",
+                info.name,
+                info.backend,
+                info.device_type
+            );
+        }
+
+        for backend in descriptor.backends() {
+            let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+                backends: backend.into(),
+                dx12_shader_compiler: wgpu::Dx12Compiler::default(),
+            });
+            let adapter = block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: descriptor.power_preference.into(),
+                force_fallback_adapter: descriptor.force_fallback_adapter,
+                compatible_surface: None,
+            }));
+            match adapter {
+                Some(adapter) => return Some((instance, adapter)),
+                None => log::warn!("no adapter found on {}", backend.name()),
+            }
+        }
+        None
+    }
+
     pub fn create_texture(
         &self,
         size: texture::Size,
         format: texture::Format,
         sampler: Option<texture::Sampler>,
         pixels: Option<&[u8]>,
+        mipmaps: bool,
     ) -> Result<Texture, Error> {
         Texture::new(
             &self.device,
@@ -133,6 +328,7 @@ impl Context {
             format,
             sampler,
             pixels,
+            mipmaps,
         )
         .map_err(|error| error.into())
     }
@@ -142,16 +338,18 @@ impl Context {
         window: &Window,
         vsync: bool,
         depth: bool,
+        sample_count: u32,
     ) -> Result<RenderTarget, Error> {
-        RenderTarget::new(
-            &self.instance,
-            &self.adapter,
-            self.device.clone(),
-            self.queue.clone(),
-            window,
-            vsync,
-            depth,
-        )
-        .map_err(|error| error.into())
+        RenderTarget::new(self, window, vsync, depth, sample_count).map_err(|error| error.into())
+    }
+
+    // draws into a texture instead of a window, for rendering without one
+    pub fn create_offscreen_render_target(
+        &self,
+        size: Extent2d<u32>,
+        depth: bool,
+        sample_count: u32,
+    ) -> Result<RenderTarget, Error> {
+        RenderTarget::new_offscreen(self, size, depth, sample_count).map_err(|error| error.into())
     }
 }
@@ -1,21 +1,42 @@
 use super::{texture, Context, DrawCommand, DrawCommandList, TargetFormat, Texture};
-use crate::{ByteArray, Bytes, Color, Deg, Matrix4, SquareMatrix, Vector2, Vector3, Zero};
-use std::{cell::RefCell, collections::HashMap, mem, rc::Rc};
+use crate::{
+    ByteArray, Bytes, Color, Deg, InnerSpace, Matrix4, SquareMatrix, Vector2, Vector3, Vector4,
+    Zero,
+};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    mem,
+    num::NonZeroU8,
+    rc::{Rc, Weak},
+};
 
 #[derive(Clone, Copy, Debug)]
 #[repr(C)]
 pub struct Vertex {
     pub position: Vector3<f32>,
+    // zero for vertices that aren't lit
+    pub normal: Vector3<f32>,
     pub color: Color<f32>,
     pub uv: Vector2<f32>,
+    // the layer of array textures, or the depth slice of 3d textures
+    pub layer: u32,
 }
 
 impl Vertex {
-    pub const fn new(position: Vector3<f32>, color: Color<f32>, uv: Vector2<f32>) -> Self {
+    pub const fn new(
+        position: Vector3<f32>,
+        normal: Vector3<f32>,
+        color: Color<f32>,
+        uv: Vector2<f32>,
+        layer: u32,
+    ) -> Self {
         Self {
             position,
+            normal,
             color,
             uv,
+            layer,
         }
     }
 }
@@ -24,17 +45,42 @@ impl Default for Vertex {
     fn default() -> Self {
         Self {
             position: Vector3::zero(),
+            normal: Vector3::zero(),
             color: Color::white(),
             uv: Vector2::zero(),
+            layer: 0,
         }
     }
 }
 
 unsafe impl Bytes for Vertex {}
 
+/// Per instance data of instanced draws, the tint multiplies the vertex colours.
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+pub struct Instance {
+    pub transform: Matrix4<f32>,
+    pub tint: Color<f32>,
+}
+
+impl Instance {
+    pub const fn new(transform: Matrix4<f32>, tint: Color<f32>) -> Self {
+        Self { transform, tint }
+    }
+}
+
+impl From<Matrix4<f32>> for Instance {
+    fn from(value: Matrix4<f32>) -> Self {
+        Self::new(value, Color::white())
+    }
+}
+
+unsafe impl Bytes for Instance {}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum BlendMode {
     Opaque,
+    Translucent,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -208,6 +254,39 @@ impl Projection {
     }
 }
 
+/// A directional sun and the ambient light that reaches every face.
+#[derive(Clone, Copy, Debug)]
+pub struct Light {
+    // the direction the sunlight travels in
+    pub sun_direction: Vector3<f32>,
+    pub sun_color: Color<f32>,
+    pub ambient_color: Color<f32>,
+}
+
+impl Light {
+    pub const fn new(
+        sun_direction: Vector3<f32>,
+        sun_color: Color<f32>,
+        ambient_color: Color<f32>,
+    ) -> Self {
+        Self {
+            sun_direction,
+            sun_color,
+            ambient_color,
+        }
+    }
+}
+
+impl Default for Light {
+    fn default() -> Self {
+        Self::new(
+            Vector3::new(-0.3, -1.0, -0.5),
+            Color::new(0.7, 0.7, 0.7, 1.0),
+            Color::new(0.35, 0.35, 0.35, 1.0),
+        )
+    }
+}
+
 impl From<Projection> for Matrix4<f32> {
     fn from(value: Projection) -> Self {
         match value {
@@ -232,7 +311,24 @@ pub struct MaterialTexture<'a> {
 struct Global {
     view: Matrix4<f32>,
     projection: Matrix4<f32>,
+    // normalized, w is unused
+    sun_direction: Vector4<f32>,
+    sun_color: Color<f32>,
+    ambient_color: Color<f32>,
+}
+
+impl Global {
+    fn set_light(&mut self, light: Light) {
+        let sun_direction = match light.sun_direction.is_zero() {
+            true => light.sun_direction,
+            false => light.sun_direction.normalize(),
+        };
+        self.sun_direction = sun_direction.extend(0.0);
+        self.sun_color = light.sun_color;
+        self.ambient_color = light.ambient_color;
+    }
 }
+
 unsafe impl Bytes for Global {}
 
 #[derive(Clone, Copy, Debug)]
@@ -243,10 +339,96 @@ struct Push {
 unsafe impl Bytes for Push {}
 unsafe impl ByteArray<64> for Push {}
 
+// per draw transforms for adapters without push constants, each bound with a dynamic offset
+#[derive(Debug)]
+struct ModelBuffer {
+    bind_group_layout: wgpu::BindGroupLayout,
+    buffer: wgpu::Buffer,
+    bind_group: Rc<wgpu::BindGroup>,
+    stride: u64,
+    capacity: usize,
+}
+
+impl ModelBuffer {
+    const INITIAL_CAPACITY: usize = 64;
+
+    fn new(device: &wgpu::Device) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: true,
+                    min_binding_size: wgpu::BufferSize::new(mem::size_of::<Push>() as u64),
+                },
+                count: None,
+            }],
+        });
+        let alignment = device.limits().min_uniform_buffer_offset_alignment as u64;
+        let stride = (mem::size_of::<Push>() as u64 + alignment - 1) & !(alignment - 1);
+        let capacity = Self::INITIAL_CAPACITY;
+        let (buffer, bind_group) =
+            Self::create_buffer(device, &bind_group_layout, stride, capacity);
+        Self {
+            bind_group_layout,
+            buffer,
+            bind_group,
+            stride,
+            capacity,
+        }
+    }
+
+    // writes one transform per draw, growing the buffer to fit them
+    fn write(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, draws: &[Push]) {
+        if draws.len() > self.capacity {
+            self.capacity = draws.len().next_power_of_two();
+            (self.buffer, self.bind_group) =
+                Self::create_buffer(device, &self.bind_group_layout, self.stride, self.capacity);
+        }
+        let mut data = vec![0; draws.len() * self.stride as usize];
+        for (push, chunk) in draws.iter().zip(data.chunks_mut(self.stride as usize)) {
+            chunk[..mem::size_of::<Push>()].copy_from_slice(push.as_bytes());
+        }
+        queue.write_buffer(&self.buffer, 0, &data);
+    }
+
+    #[inline]
+    fn create_buffer(
+        device: &wgpu::Device,
+        bind_group_layout: &wgpu::BindGroupLayout,
+        stride: u64,
+        capacity: usize,
+    ) -> (wgpu::Buffer, Rc<wgpu::BindGroup>) {
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: stride * capacity as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::UNIFORM,
+            mapped_at_creation: false,
+        });
+        let bind_group = Rc::new(device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer: &buffer,
+                    offset: 0,
+                    size: wgpu::BufferSize::new(mem::size_of::<Push>() as u64),
+                }),
+            }],
+        }));
+        (buffer, bind_group)
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Hash)]
 struct PipelineIndex {
     material: Material,
     view_dimension: wgpu::TextureViewDimension,
+    instanced: bool,
+    sample_count: u32,
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -263,13 +445,35 @@ struct DrawMeshState {
     vertex_buffer: DrawBufferState,
     index_buffer: Option<DrawBufferState>,
     texture: Rc<wgpu::Texture>,
-    sampler: Rc<wgpu::Sampler>,
+    sampler: texture::Sampler,
     push_data: Push,
+    // a range of the renderer's instances, instanced draws ignore `push_data`
+    instances: Option<(u32, u32)>,
+}
+
+// a texture's bind group, keyed by its address while it is alive
+#[derive(Debug, PartialEq, Eq, Hash)]
+struct TextureBindGroupIndex {
+    texture: *const wgpu::Texture,
+    sampler: texture::Sampler,
+    view_dimension: wgpu::TextureViewDimension,
+}
+
+// what the commands recorded so far have bound, to skip rebinding it
+#[derive(Default)]
+struct BoundState {
+    pipeline: Option<*const wgpu::RenderPipeline>,
+    texture_bind_group: Option<*const wgpu::BindGroup>,
+    push_data: Option<[u8; Renderer::PUSH_SIZE]>,
+    vertex_buffer: Option<(*const wgpu::Buffer, u64, u64)>,
+    index_buffer: Option<(*const wgpu::Buffer, u64, u64)>,
+    instance_buffer: bool,
 }
 
 #[derive(Debug, PartialEq, Eq)]
 pub enum RendererError {
     MaterialTexturesInvalid,
+    TextureFormatUnsupported,
 }
 
 #[derive(Debug)]
@@ -278,15 +482,21 @@ pub struct Renderer {
     queue: Rc<wgpu::Queue>,
     default_texture: Rc<Texture>,
     target_format: TargetFormat,
-    module: wgpu::ShaderModule,
+    source: String,
+    modules: HashMap<wgpu::TextureViewDimension, wgpu::ShaderModule>,
     global_data: Global,
     global_buffer: wgpu::Buffer,
     global_bind_group_layout: wgpu::BindGroupLayout,
     global_bind_group: Rc<wgpu::BindGroup>,
-    samplers: HashMap<texture::Sampler, Rc<wgpu::Sampler>>,
+    samplers: HashMap<texture::Sampler, wgpu::Sampler>,
+    texture_bind_groups: HashMap<TextureBindGroupIndex, (Weak<wgpu::Texture>, Rc<wgpu::BindGroup>)>,
     texture_bind_group_layouts: HashMap<wgpu::TextureViewDimension, wgpu::BindGroupLayout>,
     pipelines: HashMap<PipelineIndex, Rc<wgpu::RenderPipeline>>,
     draw_states: Vec<DrawMeshState>,
+    // `None` when the model matrix is a push constant
+    model_buffer: Option<ModelBuffer>,
+    instances: Vec<Instance>,
+    instance_buffer: Option<Rc<wgpu::Buffer>>,
 }
 
 impl Renderer {
@@ -295,6 +505,10 @@ impl Renderer {
         texture::AddressMode::ClampToEdge,
     );
     const PUSH_SIZE: usize = 64;
+    const PUSH_CONSTANT_MODEL: &'static str = "var<push_constant> model: mat4x4<f32>;";
+    const UNIFORM_MODEL: &'static str = "@group(2) @binding(0) var<uniform> model: mat4x4<f32>;";
+    const TEXTURE_D2: &'static str = "texture_2d<f32>";
+    const SAMPLE_D2: &'static str = "textureSample(texture, texture_sampler, vert_frag.uv)";
 
     fn new(
         device: Rc<wgpu::Device>,
@@ -302,12 +516,25 @@ impl Renderer {
         default_texture: Rc<Texture>,
         target_format: TargetFormat,
         projection: Projection,
+        push_constants: bool,
     ) -> Self {
-        let module = device.create_shader_module(wgpu::include_wgsl!("mesh.wgsl"));
-        let global_data = Global {
+        // the model matrix moves from a push constant to a uniform with a dynamic offset
+        let source = include_str!("mesh.wgsl");
+        debug_assert!(source.contains(Self::PUSH_CONSTANT_MODEL));
+        debug_assert!(source.contains(Self::TEXTURE_D2) && source.contains(Self::SAMPLE_D2));
+        let source = match push_constants {
+            true => source.to_owned(),
+            false => source.replace(Self::PUSH_CONSTANT_MODEL, Self::UNIFORM_MODEL),
+        };
+        let model_buffer = (!push_constants).then(|| ModelBuffer::new(&device));
+        let mut global_data = Global {
             view: Matrix4::identity(),
             projection: projection.into(),
+            sun_direction: Vector4::zero(),
+            sun_color: Color::white(),
+            ambient_color: Color::white(),
         };
+        global_data.set_light(Light::default());
         let global_buffer = device.create_buffer(&wgpu::BufferDescriptor {
             label: None,
             size: mem::size_of::<Global>() as u64,
@@ -320,7 +547,7 @@ impl Renderer {
                 label: None,
                 entries: &[wgpu::BindGroupLayoutEntry {
                     binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX,
+                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
                     ty: wgpu::BindingType::Buffer {
                         ty: wgpu::BufferBindingType::Uniform,
                         has_dynamic_offset: false,
@@ -338,23 +565,30 @@ impl Renderer {
             }],
         }));
         let samplers = HashMap::new();
+        let texture_bind_groups = HashMap::new();
         let texture_bind_group_layouts = HashMap::new();
         let pipelines = HashMap::new();
         let draw_states = Vec::new();
+        let instances = Vec::new();
         Self {
             device,
             queue,
             default_texture,
             target_format,
-            module,
+            source,
+            modules: HashMap::new(),
             global_data,
             global_buffer,
             global_bind_group_layout,
             global_bind_group,
             samplers,
+            texture_bind_groups,
             texture_bind_group_layouts,
             pipelines,
             draw_states,
+            model_buffer,
+            instances,
+            instance_buffer: None,
         }
     }
 
@@ -366,17 +600,91 @@ impl Renderer {
         self.global_data.projection = projection.into();
     }
 
+    pub fn set_light(&mut self, light: Light) {
+        self.global_data.set_light(light);
+    }
+
     pub fn draw_mesh(
         &mut self,
         transform: Matrix4<f32>,
         mesh: &Mesh,
         material_textures: &[MaterialTexture<'_>],
+    ) -> Result<(), RendererError> {
+        self.record_mesh(transform, mesh, material_textures, None)
+    }
+
+    // draws the mesh once per transform in a single draw call
+    pub fn draw_mesh_instanced(
+        &mut self,
+        mesh: &Mesh,
+        material_textures: &[MaterialTexture<'_>],
+        transforms: &[Matrix4<f32>],
+    ) -> Result<(), RendererError> {
+        let start = self.instances.len() as u32;
+        self.instances.extend(
+            transforms
+                .iter()
+                .map(|transform| Instance::from(*transform)),
+        );
+        self.record_instances(mesh, material_textures, start)
+    }
+
+    pub fn draw_mesh_instances(
+        &mut self,
+        mesh: &Mesh,
+        material_textures: &[MaterialTexture<'_>],
+        instances: &[Instance],
+    ) -> Result<(), RendererError> {
+        let start = self.instances.len() as u32;
+        self.instances.extend_from_slice(instances);
+        self.record_instances(mesh, material_textures, start)
+    }
+
+    fn record_instances(
+        &mut self,
+        mesh: &Mesh,
+        material_textures: &[MaterialTexture<'_>],
+        start: u32,
+    ) -> Result<(), RendererError> {
+        let end = self.instances.len() as u32;
+        if start == end {
+            return Ok(());
+        }
+        let result = self.record_mesh(
+            Matrix4::identity(),
+            mesh,
+            material_textures,
+            Some((start, end)),
+        );
+        if result.is_err() {
+            self.instances.truncate(start as usize);
+        }
+        result
+    }
+
+    fn record_mesh(
+        &mut self,
+        transform: Matrix4<f32>,
+        mesh: &Mesh,
+        material_textures: &[MaterialTexture<'_>],
+        instances: Option<(u32, u32)>,
     ) -> Result<(), RendererError> {
         if material_textures.len() == 0 || material_textures.len() < mesh.submeshes.len() {
             return Err(RendererError::MaterialTexturesInvalid);
         }
+        // the bind group layouts only take filterable textures
+        if material_textures
+            .iter()
+            .filter_map(|material_texture| material_texture.texture)
+            .any(|texture| !texture.format().is_filterable())
+        {
+            return Err(RendererError::TextureFormatUnsupported);
+        }
 
         mesh.flush();
+        if mesh.vertices.is_empty() {
+            return Ok(());
+        }
         let is_indexed = !mesh.submeshes.is_empty();
 
         // common draw state
@@ -398,25 +706,31 @@ impl Renderer {
         for (i, MaterialTexture { material, texture }) in material_textures.iter().enumerate() {
             let material = *material;
 
+            // empty buffer slices are invalid, so skip empty submeshes
+            if is_indexed && mesh.submeshes[i].indices.is_empty() {
+                continue;
+            }
+
             // register texture sampler
             let sampler = texture.map_or(Self::DEFAULT_SAMPLER, |texture| {
                 texture.sampler().unwrap_or(Self::DEFAULT_SAMPLER)
             });
-            let sampler = self
-                .samplers
-                .entry(sampler)
-                .or_insert_with(|| {
-                    Rc::new(self.device.create_sampler(&wgpu::SamplerDescriptor {
-                        label: None,
-                        address_mode_u: sampler.address.into(),
-                        address_mode_v: sampler.address.into(),
-                        address_mode_w: sampler.address.into(),
-                        mag_filter: sampler.filter.into(),
-                        min_filter: sampler.filter.into(),
-                        ..Default::default()
-                    }))
+            self.samplers.entry(sampler).or_insert_with(|| {
+                self.device.create_sampler(&wgpu::SamplerDescriptor {
+                    label: None,
+                    address_mode_u: sampler.address.into(),
+                    address_mode_v: sampler.address.into(),
+                    address_mode_w: sampler.address.into(),
+                    mag_filter: sampler.filter.into(),
+                    min_filter: sampler.filter.into(),
+                    mipmap_filter: sampler.mipmap_filter.into(),
+                    lod_min_clamp: sampler.lod_min as f32,
+                    lod_max_clamp: sampler.lod_max as f32,
+                    anisotropy_clamp: NonZeroU8::new(sampler.effective_anisotropy())
+                        .filter(|clamp| clamp.get() > 1),
+                    ..Default::default()
                 })
-                .clone();
+            });
 
             let view_dimension = texture.map_or(self.default_texture.view_dimension(), |texture| {
                 texture.view_dimension()
@@ -456,9 +770,12 @@ impl Renderer {
                 });
 
             // register pipeline
+            let instanced = instances.is_some();
             let pipeline_index = PipelineIndex {
                 material,
                 view_dimension,
+                instanced,
+                sample_count: self.target_format.sample_count,
             };
             let cull_mode = match material.cull {
                 None => None,
@@ -470,24 +787,59 @@ impl Renderer {
                     .depth_format
                     .map(|format| wgpu::DepthStencilState {
                         format,
-                        depth_write_enabled: true,
+                        depth_write_enabled: material.blend == BlendMode::Opaque,
                         depth_compare: wgpu::CompareFunction::Less,
                         stencil: wgpu::StencilState::default(),
                         bias: wgpu::DepthBiasState::default(),
                     });
+            let module = self.modules.entry(view_dimension).or_insert_with(|| {
+                let (texture_type, sample) = Self::get_texture_source(view_dimension);
+                let source = self
+                    .source
+                    .replace(Self::TEXTURE_D2, texture_type)
+                    .replace(Self::SAMPLE_D2, sample);
+                self.device
+                    .create_shader_module(wgpu::ShaderModuleDescriptor {
+                        label: Some("mesh.wgsl"),
+                        source: wgpu::ShaderSource::Wgsl(source.into()),
+                    })
+            });
             self.pipelines.entry(pipeline_index).or_insert_with(|| {
                     let blend = match material.blend {
                         BlendMode::Opaque => wgpu::BlendState::REPLACE,
+                        BlendMode::Translucent => wgpu::BlendState::ALPHA_BLENDING,
+                    };
+                    // instanced draws read their transforms from the instance buffer instead
+                    let mut bind_group_layouts = vec![&self.global_bind_group_layout, texture_bind_group_layout];
+                    let mut push_constant_ranges = Vec::new();
+                    match (self.model_buffer.as_ref(), instanced) {
+                        (_, true) => {}
+                        (Some(model_buffer), false) => bind_group_layouts.push(&model_buffer.bind_group_layout),
+                        (None, false) => push_constant_ranges.push(wgpu::PushConstantRange {
+                            stages: wgpu::ShaderStages::VERTEX,
+                            range: 0..Self::PUSH_SIZE as u32,
+                        }),
+                    }
+                    let vertex_buffer_layout = wgpu::VertexBufferLayout {
+                        array_stride: mem::size_of::<Vertex>() as u64,
+                        step_mode: wgpu::VertexStepMode::Vertex,
+                        attributes: &wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3, 2 => Float32x4, 3 => Float32x2, 4 => Uint32],
+                    };
+                    let instance_buffer_layout = wgpu::VertexBufferLayout {
+                        array_stride: mem::size_of::<Instance>() as u64,
+                        step_mode: wgpu::VertexStepMode::Instance,
+                        attributes: &wgpu::vertex_attr_array![5 => Float32x4, 6 => Float32x4, 7 => Float32x4, 8 => Float32x4, 9 => Float32x4],
+                    };
+                    let (entry_point, buffers) = match instanced {
+                        true => ("vs_main_instanced", vec![vertex_buffer_layout, instance_buffer_layout]),
+                        false => ("vs_main", vec![vertex_buffer_layout]),
                     };
                     let pipeline_layout =
                         self.device
                             .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                                 label: None,
-                                bind_group_layouts: &[&self.global_bind_group_layout, texture_bind_group_layout],
-                                push_constant_ranges: &[wgpu::PushConstantRange {
-                                    stages: wgpu::ShaderStages::VERTEX,
-                                    range: 0..Self::PUSH_SIZE as u32,
-                                }],
+                                bind_group_layouts: &bind_group_layouts,
+                                push_constant_ranges: &push_constant_ranges,
                             });
                     Rc::new(
                         self.device
@@ -495,13 +847,9 @@ impl Renderer {
                                 label: None,
                                 layout: Some(&pipeline_layout),
                                 vertex: wgpu::VertexState {
-                                    module: &self.module,
-                                    entry_point: "vs_main",
-                                    buffers: &[wgpu::VertexBufferLayout {
-                                        array_stride: mem::size_of::<Vertex>() as u64,
-                                        step_mode: wgpu::VertexStepMode::Vertex,
-                                        attributes: &wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x4, 2 => Float32x2],
-                                    }],
+                                    module,
+                                    entry_point,
+                                    buffers: &buffers,
                                 },
                                 primitive: wgpu::PrimitiveState {
                                     topology: wgpu::PrimitiveTopology::TriangleList,
@@ -514,12 +862,12 @@ impl Renderer {
                                 },
                                 depth_stencil,
                                 multisample: wgpu::MultisampleState {
-                                    count: 1,
+                                    count: self.target_format.sample_count,
                                     mask: !0,
                                     alpha_to_coverage_enabled: false,
                                 },
                                 fragment: Some(wgpu::FragmentState {
-                                    module: &self.module,
+                                    module,
                                     entry_point: "fs_main",
                                     targets: &[Some(wgpu::ColorTargetState {
                                         format: self.target_format.color_format,
@@ -561,90 +909,238 @@ impl Renderer {
                 view_dimension,
                 sampler,
                 push_data,
+                instances,
             };
             self.draw_states.push(draw_state);
         }
         Ok(())
     }
 
-    pub(crate) fn submit(&mut self) -> DrawCommandList<{ Self::PUSH_SIZE }> {
+    pub fn submit(&mut self) -> DrawCommandList<{ Self::PUSH_SIZE }> {
         self.queue
             .write_buffer(&self.global_buffer, 0, self.global_data.as_bytes());
+
+        // group opaque draws by pipeline and texture, translucent ones keep their order
+        self.draw_states.sort_by(|a, b| {
+            let get_key = |draw_state: &DrawMeshState| {
+                (
+                    draw_state.material,
+                    draw_state.view_dimension as u32,
+                    draw_state.instances.is_some(),
+                    Rc::as_ptr(&draw_state.texture),
+                )
+            };
+            match (a.material.blend, b.material.blend) {
+                (BlendMode::Opaque, BlendMode::Opaque) => get_key(a).cmp(&get_key(b)),
+                (a_blend, b_blend) => a_blend.cmp(&b_blend),
+            }
+        });
+        if let Some(model_buffer) = self.model_buffer.as_mut() {
+            let draws: Vec<_> = self
+                .draw_states
+                .iter()
+                .map(|draw_state| draw_state.push_data)
+                .collect();
+            model_buffer.write(&self.device, &self.queue, &draws);
+        }
+        self.write_instances();
+
+        // forget bind groups of dropped textures
+        self.texture_bind_groups
+            .retain(|_, (texture, _)| texture.strong_count() > 0);
+
         let mut draw_commands = Vec::new();
-        for draw_state in self.draw_states.drain(..) {
-            let material = (&draw_state.material).clone();
-            let view_dimension = (&draw_state.view_dimension).clone();
+        let mut state = BoundState::default();
+        draw_commands.push(DrawCommand::SetBindGroup {
+            index: 0,
+            bind_group: self.global_bind_group.clone(),
+            offsets: Vec::new(),
+        });
+        let draw_states = mem::take(&mut self.draw_states);
+        for (draw_index, draw_state) in draw_states.into_iter().enumerate() {
             let pipeline_index = PipelineIndex {
-                material,
-                view_dimension,
+                material: draw_state.material,
+                view_dimension: draw_state.view_dimension,
+                instanced: draw_state.instances.is_some(),
+                sample_count: self.target_format.sample_count,
             };
-            let pipeline = self.pipelines[&pipeline_index].clone();
-            let texture_bind_group_layout = &self.texture_bind_group_layouts[&view_dimension];
-            let texture = (&draw_state.texture).clone();
-            let texture_view = texture.create_view(&wgpu::TextureViewDescriptor::default());
-            let sampler = (&draw_state.sampler).clone();
-            let texture_bind_group =
-                Rc::new(self.device.create_bind_group(&wgpu::BindGroupDescriptor {
-                    label: None,
-                    layout: texture_bind_group_layout,
-                    entries: &[
-                        wgpu::BindGroupEntry {
-                            binding: 0,
-                            resource: wgpu::BindingResource::TextureView(&texture_view),
-                        },
-                        wgpu::BindGroupEntry {
-                            binding: 1,
-                            resource: wgpu::BindingResource::Sampler(&sampler),
-                        },
-                    ],
-                }));
-            let push_data = draw_state.push_data.as_byte_array().clone();
-            let buffer = draw_state.buffer.clone();
-            let vertex_buffer_range = draw_state.vertex_buffer.range;
-            let vertex_count = draw_state.vertex_buffer.element_count;
-            draw_commands.push(DrawCommand::SetPipeline(pipeline));
-            draw_commands.push(DrawCommand::SetBindGroup {
-                index: 0,
-                bind_group: self.global_bind_group.clone(),
-            });
-            draw_commands.push(DrawCommand::SetBindGroup {
-                index: 1,
-                bind_group: texture_bind_group,
-            });
-            draw_commands.push(DrawCommand::SetPushConstant {
-                stages: wgpu::ShaderStages::VERTEX,
-                offset: 0,
-                data: push_data,
-            });
-            draw_commands.push(DrawCommand::SetVertexBuffer {
-                buffer: buffer.clone(),
-                start: vertex_buffer_range.0,
-                end: vertex_buffer_range.1,
-            });
+            let pipeline = &self.pipelines[&pipeline_index];
+            if state.pipeline.replace(Rc::as_ptr(pipeline)) != Some(Rc::as_ptr(pipeline)) {
+                draw_commands.push(DrawCommand::SetPipeline(pipeline.clone()));
+                // every pipeline has its own layout, and switching layouts clears push constants
+                state.push_data = None;
+            }
+
+            let texture_bind_group = self.get_texture_bind_group(&draw_state);
+            let bind_group_ptr = Rc::as_ptr(&texture_bind_group);
+            if state.texture_bind_group.replace(bind_group_ptr) != Some(bind_group_ptr) {
+                draw_commands.push(DrawCommand::SetBindGroup {
+                    index: 1,
+                    bind_group: texture_bind_group,
+                    offsets: Vec::new(),
+                });
+            }
+
+            match (self.model_buffer.as_ref(), draw_state.instances) {
+                (_, Some(_)) => {
+                    if !state.instance_buffer {
+                        state.instance_buffer = true;
+                        let buffer = self.instance_buffer.clone().unwrap();
+                        let end = (self.instances.len() * mem::size_of::<Instance>()) as u64;
+                        draw_commands.push(DrawCommand::SetInstanceBuffer {
+                            buffer,
+                            start: 0,
+                            end,
+                        });
+                    }
+                }
+                (Some(model_buffer), None) => draw_commands.push(DrawCommand::SetBindGroup {
+                    index: 2,
+                    bind_group: model_buffer.bind_group.clone(),
+                    offsets: vec![(draw_index as u64 * model_buffer.stride) as u32],
+                }),
+                (None, None) => {
+                    let push_data = *draw_state.push_data.as_byte_array();
+                    if state.push_data.replace(push_data) != Some(push_data) {
+                        draw_commands.push(DrawCommand::SetPushConstant {
+                            stages: wgpu::ShaderStages::VERTEX,
+                            offset: 0,
+                            data: push_data,
+                        });
+                    }
+                }
+            }
+
+            let buffer = draw_state.buffer;
+            let (start, end) = draw_state.vertex_buffer.range;
+            let vertex_buffer = (Rc::as_ptr(&buffer), start, end);
+            if state.vertex_buffer.replace(vertex_buffer) != Some(vertex_buffer) {
+                draw_commands.push(DrawCommand::SetVertexBuffer {
+                    buffer: buffer.clone(),
+                    start,
+                    end,
+                });
+            }
             if let Some(index_buffer) = draw_state.index_buffer {
-                let index_range = index_buffer.range;
-                let index_count = index_buffer.element_count;
-                draw_commands.push(DrawCommand::SetIndexBuffer {
-                    buffer,
-                    start: index_range.0,
-                    end: index_range.1,
+                let (start, end) = index_buffer.range;
+                let index_range = (Rc::as_ptr(&buffer), start, end);
+                if state.index_buffer.replace(index_range) != Some(index_range) {
+                    draw_commands.push(DrawCommand::SetIndexBuffer { buffer, start, end });
+                }
+                let end = index_buffer.element_count;
+                draw_commands.push(match draw_state.instances {
+                    Some(instances) => DrawCommand::DrawIndexedInstanced {
+                        start: 0,
+                        end,
+                        instances,
+                    },
+                    None => DrawCommand::DrawIndexed { start: 0, end },
                 });
-                draw_commands.push(DrawCommand::DrawIndexed {
-                    start: 0,
-                    end: index_count,
-                })
             } else {
-                draw_commands.push(DrawCommand::Draw {
-                    start: 0,
-                    end: vertex_count,
+                let end = draw_state.vertex_buffer.element_count;
+                draw_commands.push(match draw_state.instances {
+                    Some(instances) => DrawCommand::DrawInstanced {
+                        start: 0,
+                        end,
+                        instances,
+                    },
+                    None => DrawCommand::Draw { start: 0, end },
                 });
             }
         }
+        self.instances.clear();
         DrawCommandList {
             target_format: self.target_format,
             draw_commands,
         }
     }
+
+    // uploads this frame's instances, growing the buffer to fit them
+    fn write_instances(&mut self) {
+        if self.instances.is_empty() {
+            return;
+        }
+        let instances = self.instances.as_slice();
+        let size = instances.as_bytes().len() as u64;
+        let fits = match self.instance_buffer.as_ref() {
+            Some(buffer) => buffer.size() >= size,
+            None => false,
+        };
+        if !fits {
+            self.instance_buffer = Some(Rc::new(self.device.create_buffer(
+                &wgpu::BufferDescriptor {
+                    label: None,
+                    size: size.next_power_of_two(),
+                    usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::VERTEX,
+                    mapped_at_creation: false,
+                },
+            )));
+        }
+        if let Some(buffer) = self.instance_buffer.as_ref() {
+            self.queue.write_buffer(buffer, 0, instances.as_bytes());
+        }
+    }
+
+    // the texture type and sampling of mesh.wgsl's fragment shader for each view dimension
+    fn get_texture_source(
+        view_dimension: wgpu::TextureViewDimension,
+    ) -> (&'static str, &'static str) {
+        match view_dimension {
+            wgpu::TextureViewDimension::D2 => (Self::TEXTURE_D2, Self::SAMPLE_D2),
+            wgpu::TextureViewDimension::D2Array => (
+                "texture_2d_array<f32>",
+                "textureSample(texture, texture_sampler, vert_frag.uv, i32(vert_frag.layer))",
+            ),
+            wgpu::TextureViewDimension::D3 => (
+                "texture_3d<f32>",
+                "textureSample(texture, texture_sampler, vec3<f32>(vert_frag.uv, (f32(vert_frag.layer) + 0.5) / f32(textureDimensions(texture).z)))",
+            ),
+            wgpu::TextureViewDimension::Cube => (
+                "texture_cube<f32>",
+                "textureSample(texture, texture_sampler, vert_frag.direction)",
+            ),
+            wgpu::TextureViewDimension::D1 | wgpu::TextureViewDimension::CubeArray => {
+                unreachable!("textures never have this view dimension")
+            }
+        }
+    }
+
+    // bind groups live as long as their texture, so views aren't recreated every frame
+    fn get_texture_bind_group(&mut self, draw_state: &DrawMeshState) -> Rc<wgpu::BindGroup> {
+        let index = TextureBindGroupIndex {
+            texture: Rc::as_ptr(&draw_state.texture),
+            sampler: draw_state.sampler,
+            view_dimension: draw_state.view_dimension,
+        };
+        if let Some((_, bind_group)) = self.texture_bind_groups.get(&index) {
+            return bind_group.clone();
+        }
+        // cube and single layer array views aren't inferred from the texture
+        let texture_view = draw_state
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor {
+                dimension: Some(draw_state.view_dimension),
+                ..Default::default()
+            });
+        let bind_group = Rc::new(self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &self.texture_bind_group_layouts[&draw_state.view_dimension],
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&texture_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.samplers[&draw_state.sampler]),
+                },
+            ],
+        }));
+        let texture = Rc::downgrade(&draw_state.texture);
+        self.texture_bind_groups
+            .insert(index, (texture, bind_group.clone()));
+        bind_group
+    }
 }
 
 impl Context {
@@ -652,7 +1148,7 @@ impl Context {
         Mesh::new(self.device.clone(), self.queue.clone(), vertices, indices)
     }
 
-    pub(crate) fn create_mesh_renderer(
+    pub fn create_mesh_renderer(
         &self,
         target_format: TargetFormat,
         projection: Projection,
@@ -663,6 +1159,7 @@ impl Context {
             self.default_texture.clone(),
             target_format,
             projection,
+            self.push_constants,
         )
     }
 }
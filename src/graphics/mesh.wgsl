@@ -1,29 +1,71 @@
 struct Vert {
     @location(0) position: vec3<f32>,
-    @location(1) color: vec4<f32>,
-    @location(2) uv: vec2<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) color: vec4<f32>,
+    @location(3) uv: vec2<f32>,
+    @location(4) layer: u32,
 }
 
 struct VertFrag {
     @builtin(position) out_position: vec4<f32>,
     @location(0) color: vec4<f32>,
     @location(1) uv: vec2<f32>,
+    @location(2) @interpolate(flat) layer: u32,
+    @location(3) direction: vec3<f32>,
+    @location(4) normal: vec3<f32>,
 }
 
 struct Global {
     view: mat4x4<f32>,
     projection: mat4x4<f32>,
+    sun_direction: vec4<f32>,
+    sun_color: vec4<f32>,
+    ambient_color: vec4<f32>,
 }
 
 @group(0) @binding(0) var<uniform> global: Global;
 var<push_constant> model: mat4x4<f32>;
 
+// the cofactor matrix is the inverse transpose scaled by the determinant, so normals stay
+// perpendicular under non-uniform scales, the fragment shader normalizes the scale away
+fn transform_normal(transform: mat4x4<f32>, normal: vec3<f32>) -> vec3<f32> {
+    let x = transform[0].xyz;
+    let y = transform[1].xyz;
+    let z = transform[2].xyz;
+    let cofactor = mat3x3<f32>(cross(y, z), cross(z, x), cross(x, y));
+    return cofactor * normal * sign(dot(x, cross(y, z)));
+}
+
 @vertex
 fn vs_main(vert: Vert) -> VertFrag {
     var vert_frag: VertFrag;
     vert_frag.out_position = global.projection * global.view * model * vec4<f32>(vert.position, 1.0);
     vert_frag.color = vert.color;
     vert_frag.uv = vert.uv;
+    vert_frag.layer = vert.layer;
+    vert_frag.direction = vert.position;
+    vert_frag.normal = transform_normal(model, vert.normal);
+    return vert_frag;
+}
+
+struct Instance {
+    @location(5) model_0: vec4<f32>,
+    @location(6) model_1: vec4<f32>,
+    @location(7) model_2: vec4<f32>,
+    @location(8) model_3: vec4<f32>,
+    @location(9) tint: vec4<f32>,
+}
+
+@vertex
+fn vs_main_instanced(vert: Vert, instance: Instance) -> VertFrag {
+    let instance_model = mat4x4<f32>(instance.model_0, instance.model_1, instance.model_2, instance.model_3);
+    var vert_frag: VertFrag;
+    vert_frag.out_position = global.projection * global.view * instance_model * vec4<f32>(vert.position, 1.0);
+    vert_frag.color = vert.color * instance.tint;
+    vert_frag.uv = vert.uv;
+    vert_frag.layer = vert.layer;
+    vert_frag.direction = vert.position;
+    vert_frag.normal = transform_normal(instance_model, vert.normal);
     return vert_frag;
 }
 
@@ -38,6 +80,12 @@ struct Frag {
 fn fs_main(vert_frag: VertFrag) -> Frag {
     var frag: Frag;
     let texture_color = textureSample(texture, texture_sampler, vert_frag.uv);
-    frag.out_color = vert_frag.color * texture_color;
+    // lambert diffuse lighting, vertices without a normal are left unlit
+    let normal_length = length(vert_frag.normal);
+    let normal = vert_frag.normal / max(normal_length, 0.0001);
+    let diffuse = max(dot(normal, -global.sun_direction.xyz), 0.0);
+    let light = global.ambient_color.rgb + global.sun_color.rgb * diffuse;
+    let lit = select(light, vec3<f32>(1.0), normal_length == 0.0);
+    frag.out_color = vert_frag.color * texture_color * vec4<f32>(lit, 1.0);
     return frag;
 }
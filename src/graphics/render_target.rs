@@ -1,8 +1,13 @@
 use crate::{Bytes, Color, Extent2d};
-use std::rc::Rc;
+use std::{
+    cell::{Cell, RefCell},
+    num::NonZeroU32,
+    rc::Rc,
+    sync::mpsc,
+};
 use winit::window::Window;
 
-use super::{DrawCommand, DrawCommandList, TargetFormat};
+use super::{texture, Context, DrawCommand, DrawCommandList, TargetFormat, Texture};
 
 #[derive(Debug, PartialEq, Eq)]
 pub enum Error {
@@ -11,29 +16,55 @@ pub enum Error {
     AcquireTextureFailed(wgpu::SurfaceError),
     SizeInvalid,
     CommandsInvalid,
+    ReadbackUnsupported,
+    ReadbackFailed(wgpu::BufferAsyncError),
+    SampleCountUnsupported(u32),
+}
+
+// what a render target draws into, a window's surface or a texture that can be read back
+#[derive(Debug)]
+enum Output {
+    Surface {
+        surface: wgpu::Surface,
+        surface_config: wgpu::SurfaceConfiguration,
+    },
+    Texture(Texture),
 }
 
 #[derive(Debug)]
 pub struct RenderTarget {
     device: Rc<wgpu::Device>,
     queue: Rc<wgpu::Queue>,
-    surface: wgpu::Surface,
-    surface_config: wgpu::SurfaceConfiguration,
+    output: Output,
+    sample_count: u32,
+    // drawn into instead of the output when multisampled, then resolved into it
+    multisample_attachment: Option<wgpu::TextureView>,
     depth_attachment: Option<(wgpu::TextureFormat, wgpu::TextureView)>,
+    capture_requested: Cell<bool>,
+    capture: RefCell<Option<image::RgbaImage>>,
 }
 
 impl RenderTarget {
+    // matches the sRGB surfaces windows usually get, so offscreen images look the same
+    const OFFSCREEN_FORMAT: texture::Format = texture::Format::Rgba8UnormSrgb;
+    const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth24PlusStencil8;
+
     pub(super) fn new(
-        instance: &wgpu::Instance,
-        adapter: &wgpu::Adapter,
-        device: Rc<wgpu::Device>,
-        queue: Rc<wgpu::Queue>,
+        context: &Context,
         window: &Window,
         vsync: bool,
         depth: bool,
+        sample_count: u32,
     ) -> Result<Self, Error> {
+        let Context {
+            adapter,
+            device,
+            queue,
+            ..
+        } = context;
         let surface = unsafe {
-            instance
+            context
+                .instance
                 .create_surface(window)
                 .map_err(|error| Error::CreateSurfaceFailed(error))?
         };
@@ -46,33 +77,113 @@ impl RenderTarget {
         } else {
             wgpu::PresentMode::AutoNoVsync
         };
-        surface.configure(&device, &surface_config);
+        Self::check_sample_count(adapter, device, surface_config.format, sample_count)?;
+        surface.configure(device, &surface_config);
+        let multisample_attachment = Self::create_multisample_attachment(
+            device,
+            window_size.into(),
+            surface_config.format,
+            sample_count,
+        );
         let depth_attachment = if depth {
-            Some(Self::create_depth_attachment(&device, window_size.into()))
+            Some(Self::create_depth_attachment(
+                device,
+                window_size.into(),
+                sample_count,
+            ))
         } else {
             None
         };
         Ok(Self {
+            device: device.clone(),
+            queue: queue.clone(),
+            output: Output::Surface {
+                surface,
+                surface_config,
+            },
+            sample_count,
+            multisample_attachment,
+            depth_attachment,
+            capture_requested: Cell::new(false),
+            capture: RefCell::new(None),
+        })
+    }
+
+    pub(super) fn new_offscreen(
+        context: &Context,
+        size: Extent2d<u32>,
+        depth: bool,
+        sample_count: u32,
+    ) -> Result<Self, Error> {
+        let Context {
+            adapter,
             device,
             queue,
-            surface,
-            surface_config,
+            ..
+        } = context;
+        let format = Self::OFFSCREEN_FORMAT.into();
+        Self::check_sample_count(adapter, device, format, sample_count)?;
+        let texture = Self::create_color_texture(device, queue, size)?;
+        let multisample_attachment =
+            Self::create_multisample_attachment(device, size, format, sample_count);
+        let depth_attachment = if depth {
+            Some(Self::create_depth_attachment(device, size, sample_count))
+        } else {
+            None
+        };
+        Ok(Self {
+            device: device.clone(),
+            queue: queue.clone(),
+            output: Output::Texture(texture),
+            sample_count,
+            multisample_attachment,
             depth_attachment,
+            capture_requested: Cell::new(false),
+            capture: RefCell::new(None),
         })
     }
 
-    pub(crate) fn target_format(&self) -> TargetFormat {
+    pub fn size(&self) -> Extent2d<u32> {
+        match &self.output {
+            Output::Surface { surface_config, .. } => {
+                Extent2d::new(surface_config.width, surface_config.height)
+            }
+            Output::Texture(texture) => {
+                let size = texture.size();
+                Extent2d::new(size.width, size.height)
+            }
+        }
+    }
+
+    // the texture offscreen targets draw into, `None` for window surfaces
+    pub fn texture(&self) -> Option<&Texture> {
+        match &self.output {
+            Output::Surface { .. } => None,
+            Output::Texture(texture) => Some(texture),
+        }
+    }
+
+    pub fn target_format(&self) -> TargetFormat {
+        let color_format = match &self.output {
+            Output::Surface { surface_config, .. } => surface_config.format,
+            Output::Texture(texture) => texture.format().into(),
+        };
         TargetFormat {
-            color_format: self.surface_config.format,
+            color_format,
             depth_format: self
                 .depth_attachment
                 .as_ref()
                 .map(|(format, _)| format)
                 .copied(),
+            sample_count: self.sample_count,
         }
     }
 
-    pub(crate) fn draw_pass<const PUSH_SIZE: usize, I: Into<DrawCommandList<PUSH_SIZE>>>(
+    pub fn sample_count(&self) -> u32 {
+        self.sample_count
+    }
+
+    pub fn draw_pass<const PUSH_SIZE: usize, I: Into<DrawCommandList<PUSH_SIZE>>>(
         &self,
         clear_color: Option<Color<f64>>,
         clear_depth: Option<f32>,
@@ -82,13 +193,74 @@ impl RenderTarget {
         if command_list.target_format != self.target_format() {
             return Err(Error::CommandsInvalid);
         }
-        let surface_texture = self
-            .surface
-            .get_current_texture()
-            .map_err(|error| Error::AcquireTextureFailed(error))?;
-        let surface_texture_view = surface_texture
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
+        let surface_texture = match &self.output {
+            Output::Surface { surface, .. } => Some(
+                surface
+                    .get_current_texture()
+                    .map_err(|error| Error::AcquireTextureFailed(error))?,
+            ),
+            Output::Texture(_) => None,
+        };
+        let color_texture = match (&self.output, surface_texture.as_ref()) {
+            (Output::Texture(texture), _) => texture.handle(),
+            (_, Some(surface_texture)) => &surface_texture.texture,
+            (_, None) => unreachable!(),
+        };
+        let color_texture_view = color_texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let color_format = self.target_format().color_format;
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+
+        // surfaces may not allow copying out of them, so captured frames are drawn a second
+        // time into a texture that does
+        let capture_requested = self.capture_requested.take();
+        let capture_texture = match capture_requested && surface_texture.is_some() {
+            true => Some(self.create_capture_texture(color_format)),
+            false => None,
+        };
+        if let Some(capture_texture) = capture_texture.as_ref() {
+            let capture_view = capture_texture.create_view(&wgpu::TextureViewDescriptor::default());
+            self.encode_pass(
+                &mut encoder,
+                &capture_view,
+                clear_color,
+                clear_depth,
+                &command_list.draw_commands,
+            );
+        }
+        self.encode_pass(
+            &mut encoder,
+            &color_texture_view,
+            clear_color,
+            clear_depth,
+            &command_list.draw_commands,
+        );
+        self.queue.submit(Some(encoder.finish()));
+
+        // read the frame back before it is handed to the window
+        let capture = match capture_requested {
+            true => {
+                let texture = capture_texture.as_ref().unwrap_or(color_texture);
+                self.read_texture(texture, color_format)
+                    .map(|image| *self.capture.borrow_mut() = Some(image))
+            }
+            false => Ok(()),
+        };
+        if let Some(surface_texture) = surface_texture {
+            surface_texture.present();
+        }
+        capture
+    }
+
+    fn encode_pass<const PUSH_SIZE: usize>(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        color_texture_view: &wgpu::TextureView,
+        clear_color: Option<Color<f64>>,
+        clear_depth: Option<f32>,
+        draw_commands: &[DrawCommand<PUSH_SIZE>],
+    ) {
         let color_ops = {
             let load = match clear_color {
                 Some(color) => wgpu::LoadOp::Clear(wgpu::Color::from(color)),
@@ -111,78 +283,292 @@ impl RenderTarget {
                 stencil_ops: None,
             }
         });
-        let mut encoder = self
-            .device
-            .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
-        {
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: None,
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &surface_texture_view,
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: None,
+            color_attachments: &[Some(match self.multisample_attachment.as_ref() {
+                Some(multisample_view) => wgpu::RenderPassColorAttachment {
+                    view: multisample_view,
+                    resolve_target: Some(color_texture_view),
+                    ops: color_ops,
+                },
+                None => wgpu::RenderPassColorAttachment {
+                    view: color_texture_view,
                     resolve_target: None,
                     ops: color_ops,
-                })],
-                depth_stencil_attachment,
-            });
-            for draw_command in command_list.draw_commands.iter() {
-                match draw_command {
-                    DrawCommand::SetPipeline(pipeline) => render_pass.set_pipeline(pipeline),
-                    DrawCommand::SetBindGroup { index, bind_group } => {
-                        render_pass.set_bind_group(*index, bind_group, &[])
-                    }
-                    DrawCommand::SetPushConstant {
-                        stages,
-                        offset,
-                        data,
-                    } => {
-                        render_pass.set_push_constants(*stages, *offset, data.as_slice().as_bytes())
-                    }
-                    DrawCommand::SetVertexBuffer { buffer, start, end } => {
-                        render_pass.set_vertex_buffer(0, buffer.slice(*start..*end))
-                    }
-                    DrawCommand::SetIndexBuffer { buffer, start, end } => render_pass
-                        .set_index_buffer(buffer.slice(*start..*end), wgpu::IndexFormat::Uint32),
-                    DrawCommand::Draw { start, end } => render_pass.draw(*start..*end, 0..1),
-                    DrawCommand::DrawIndexed { start, end } => {
-                        render_pass.draw_indexed(*start..*end, 0, 0..1)
-                    }
+                },
+            })],
+            depth_stencil_attachment,
+        });
+        for draw_command in draw_commands.iter() {
+            match draw_command {
+                DrawCommand::SetPipeline(pipeline) => render_pass.set_pipeline(pipeline),
+                DrawCommand::SetBindGroup {
+                    index,
+                    bind_group,
+                    offsets,
+                } => render_pass.set_bind_group(*index, bind_group, offsets),
+                DrawCommand::SetPushConstant {
+                    stages,
+                    offset,
+                    data,
+                } => render_pass.set_push_constants(*stages, *offset, data.as_slice().as_bytes()),
+                DrawCommand::SetVertexBuffer { buffer, start, end } => {
+                    render_pass.set_vertex_buffer(0, buffer.slice(*start..*end))
+                }
+                DrawCommand::SetIndexBuffer { buffer, start, end } => render_pass
+                    .set_index_buffer(buffer.slice(*start..*end), wgpu::IndexFormat::Uint32),
+                DrawCommand::Draw { start, end } => render_pass.draw(*start..*end, 0..1),
+                DrawCommand::DrawIndexed { start, end } => {
+                    render_pass.draw_indexed(*start..*end, 0, 0..1)
                 }
+                DrawCommand::SetInstanceBuffer { buffer, start, end } => {
+                    render_pass.set_vertex_buffer(1, buffer.slice(*start..*end))
+                }
+                DrawCommand::DrawInstanced {
+                    start,
+                    end,
+                    instances: (first, last),
+                } => render_pass.draw(*start..*end, *first..*last),
+                DrawCommand::DrawIndexedInstanced {
+                    start,
+                    end,
+                    instances: (first, last),
+                } => render_pass.draw_indexed(*start..*end, 0, *first..*last),
             }
         }
+    }
+
+    #[inline]
+    fn create_capture_texture(&self, format: wgpu::TextureFormat) -> wgpu::Texture {
+        let size = self.size();
+        self.device.create_texture(&wgpu::TextureDescriptor {
+            label: None,
+            size: wgpu::Extent3d {
+                width: size.width,
+                height: size.height,
+                ..Default::default()
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        })
+    }
+
+    /// Reads back the next frame drawn by [`RenderTarget::draw_pass`], available afterwards from
+    /// [`RenderTarget::take_capture`]. Passes that don't clear only capture their own draws.
+    pub fn capture_next_frame(&self) {
+        self.capture_requested.set(true);
+    }
+
+    pub fn take_capture(&self) -> Option<image::RgbaImage> {
+        self.capture.borrow_mut().take()
+    }
+
+    /// Copies what was last drawn into an offscreen target back to the CPU, blocking until the
+    /// GPU has finished drawing it.
+    pub fn read_pixels(&self) -> Result<image::RgbaImage, Error> {
+        let Output::Texture(texture) = &self.output else { return Err(Error::ReadbackUnsupported); };
+        self.read_texture(texture.handle(), self.target_format().color_format)
+    }
+
+    // copies a whole 8 bit RGBA or BGRA texture into an RGBA image
+    fn read_texture(
+        &self,
+        texture: &wgpu::Texture,
+        format: wgpu::TextureFormat,
+    ) -> Result<image::RgbaImage, Error> {
+        let swizzle = match format {
+            wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => false,
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => true,
+            _ => return Err(Error::ReadbackUnsupported),
+        };
+        let size = self.size();
+        let row_size = size.width * 4;
+        let alignment = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let padded_row_size = (row_size + alignment - 1) & !(alignment - 1);
+        let buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: padded_row_size as u64 * size.height as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: NonZeroU32::new(padded_row_size),
+                    rows_per_image: NonZeroU32::new(size.height),
+                },
+            },
+            wgpu::Extent3d {
+                width: size.width,
+                height: size.height,
+                ..Default::default()
+            },
+        );
         self.queue.submit(Some(encoder.finish()));
-        surface_texture.present();
-        Ok(())
+
+        let slice = buffer.slice(..);
+        let (sender, receiver) = mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        self.device.poll(wgpu::Maintain::Wait);
+        receiver
+            .recv()
+            .map_err(|_| Error::ReadbackFailed(wgpu::BufferAsyncError))?
+            .map_err(Error::ReadbackFailed)?;
+
+        // drop the row padding
+        let mut pixels = Vec::with_capacity((row_size * size.height) as usize);
+        for row in slice.get_mapped_range().chunks(padded_row_size as usize) {
+            pixels.extend_from_slice(&row[..row_size as usize]);
+        }
+        buffer.unmap();
+        if swizzle {
+            for pixel in pixels.chunks_exact_mut(4) {
+                pixel.swap(0, 2);
+            }
+        }
+        Ok(image::RgbaImage::from_raw(size.width, size.height, pixels).unwrap())
     }
 
-    pub(crate) fn set_size(&mut self, size: Extent2d<u32>) -> Result<(), Error> {
+    pub fn set_size(&mut self, size: Extent2d<u32>) -> Result<(), Error> {
         if !size.is_valid() {
             return Err(Error::SizeInvalid);
         }
-        self.surface_config.width = size.width;
-        self.surface_config.height = size.height;
-        self.surface.configure(&self.device, &self.surface_config);
+        match &mut self.output {
+            Output::Surface {
+                surface,
+                surface_config,
+            } => {
+                surface_config.width = size.width;
+                surface_config.height = size.height;
+                surface.configure(&self.device, surface_config);
+            }
+            Output::Texture(texture) => {
+                *texture = Self::create_color_texture(&self.device, &self.queue, size)?;
+            }
+        }
+        let color_format = self.target_format().color_format;
+        self.multisample_attachment = Self::create_multisample_attachment(
+            &self.device,
+            size,
+            color_format,
+            self.sample_count,
+        );
         if let Some((format, texture_view)) = self.depth_attachment.as_mut() {
-            (*format, *texture_view) = Self::create_depth_attachment(&self.device, size);
+            (*format, *texture_view) =
+                Self::create_depth_attachment(&self.device, size, self.sample_count);
         }
         Ok(())
     }
 
+    // the colour format has to resolve, and both attachments need the sample count
+    fn check_sample_count(
+        adapter: &wgpu::Adapter,
+        device: &wgpu::Device,
+        color_format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) -> Result<(), Error> {
+        if sample_count == 1 {
+            return Ok(());
+        }
+        // without adapter specific features, only the formats' guaranteed features can be used
+        let adapter_specific = device
+            .features()
+            .contains(wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES);
+        let get_flags = |format: wgpu::TextureFormat| match adapter_specific {
+            true => adapter.get_texture_format_features(format).flags,
+            false => format.describe().guaranteed_format_features.flags,
+        };
+        let color_flags = get_flags(color_format);
+        let depth_flags = get_flags(Self::DEPTH_FORMAT);
+        if color_flags.contains(wgpu::TextureFormatFeatureFlags::MULTISAMPLE_RESOLVE)
+            && color_flags.sample_count_supported(sample_count)
+            && depth_flags.sample_count_supported(sample_count)
+        {
+            Ok(())
+        } else {
+            Err(Error::SampleCountUnsupported(sample_count))
+        }
+    }
+
+    #[inline]
+    fn create_color_texture(
+        device: &wgpu::Device,
+        queue: &Rc<wgpu::Queue>,
+        size: Extent2d<u32>,
+    ) -> Result<Texture, Error> {
+        let usage = wgpu::TextureUsages::COPY_DST
+            | wgpu::TextureUsages::TEXTURE_BINDING
+            | wgpu::TextureUsages::RENDER_ATTACHMENT
+            | wgpu::TextureUsages::COPY_SRC;
+        Texture::with_usage(
+            device,
+            queue.clone(),
+            texture::Size::D2(size),
+            Self::OFFSCREEN_FORMAT,
+            None,
+            usage,
+            false,
+        )
+        .map_err(|_| Error::SizeInvalid)
+    }
+
+    #[inline]
+    fn create_multisample_attachment(
+        device: &wgpu::Device,
+        size: Extent2d<u32>,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) -> Option<wgpu::TextureView> {
+        if sample_count == 1 {
+            return None;
+        }
+        let size = wgpu::Extent3d {
+            width: size.width,
+            height: size.height,
+            ..Default::default()
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: None,
+            size,
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        Some(texture.create_view(&wgpu::TextureViewDescriptor::default()))
+    }
+
     #[inline]
     fn create_depth_attachment(
         device: &wgpu::Device,
         size: Extent2d<u32>,
+        sample_count: u32,
     ) -> (wgpu::TextureFormat, wgpu::TextureView) {
         let size = wgpu::Extent3d {
             width: size.width,
             height: size.height,
             ..Default::default()
         };
-        let format = wgpu::TextureFormat::Depth24PlusStencil8;
+        let format = Self::DEPTH_FORMAT;
         let texture = device.create_texture(&wgpu::TextureDescriptor {
             label: None,
             size,
             mip_level_count: 1,
-            sample_count: 1,
+            sample_count,
             dimension: wgpu::TextureDimension::D2,
             format,
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
@@ -194,3 +580,80 @@ impl RenderTarget {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::{Descriptor, DrawCommandList};
+    use std::panic;
+
+    const CLEAR_COLOR: Color<f64> = Color::new(1.0, 1.0, 0.0, 1.0);
+    const CLEAR_PIXEL: [u8; 4] = [0xFF, 0xFF, 0x00, 0xFF];
+
+    // `None` on machines without a usable adapter, where there is nothing to test. Some GL
+    // drivers panic creating a device without a display rather than failing
+    fn new_context() -> Option<Context> {
+        match panic::catch_unwind(|| Context::new(Descriptor::default())) {
+            Ok(Ok(context)) => Some(context),
+            Ok(Err(error)) => {
+                eprintln!("skipped, no graphics context: {error:?}");
+                None
+            }
+            Err(_) => {
+                eprintln!("skipped, creating a graphics context panicked");
+                None
+            }
+        }
+    }
+
+    fn clear(target: &RenderTarget) {
+        let commands = DrawCommandList::<0> {
+            target_format: target.target_format(),
+            draw_commands: Vec::new(),
+        };
+        target
+            .draw_pass(Some(CLEAR_COLOR), Some(1.0), commands)
+            .unwrap();
+    }
+
+    #[test]
+    fn clear_and_read_back() {
+        let Some(context) = new_context() else { return; };
+        // a width whose rows need padding for the copy
+        let size = Extent2d::new(5, 3);
+        for (depth, sample_count) in [(false, 1), (true, 1), (true, 4)] {
+            let target = context
+                .create_offscreen_render_target(size, depth, sample_count)
+                .unwrap();
+            clear(&target);
+            let image = target.read_pixels().unwrap();
+            assert_eq!(image.dimensions(), (size.width, size.height));
+            assert!(image.pixels().all(|pixel| pixel.0 == CLEAR_PIXEL));
+        }
+    }
+
+    #[test]
+    fn read_back_bgra() {
+        let Some(context) = new_context() else { return; };
+        let target = context
+            .create_offscreen_render_target(Extent2d::new(5, 3), false, 1)
+            .unwrap();
+        // BGRA is what window surfaces usually are, so it's read through the capture path
+        let format = wgpu::TextureFormat::Bgra8UnormSrgb;
+        let texture = target.create_capture_texture(format);
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let mut encoder = target
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        target.encode_pass::<0>(&mut encoder, &view, Some(CLEAR_COLOR), None, &[]);
+        target.queue.submit(Some(encoder.finish()));
+        let image = target.read_texture(&texture, format).unwrap();
+        assert!(image.pixels().all(|pixel| pixel.0 == CLEAR_PIXEL));
+
+        let format = wgpu::TextureFormat::Rgba16Float;
+        assert_eq!(
+            target.read_texture(&texture, format).err(),
+            Some(Error::ReadbackUnsupported)
+        );
+    }
+}
@@ -1,47 +1,169 @@
 use crate::{Extent2d, Extent3d, Offset3d};
-use std::{num::NonZeroU32, rc::Rc};
+use std::{cell::RefCell, num::NonZeroU32, rc::Rc};
 
 impl Sampler {
     pub const fn new(filter: FilterMode, address: AddressMode) -> Self {
-        Self { filter, address }
+        Self {
+            filter,
+            address,
+            mipmap_filter: FilterMode::Nearest,
+            lod_min: 0,
+            lod_max: u32::MAX,
+            anisotropy: 1,
+        }
+    }
+
+    pub const fn with_mipmap_filter(self, mipmap_filter: FilterMode) -> Self {
+        Self {
+            mipmap_filter,
+            ..self
+        }
+    }
+
+    // the first and last mip level that are sampled
+    pub const fn with_lod_clamp(self, lod_min: u32, lod_max: u32) -> Self {
+        Self {
+            lod_min,
+            lod_max,
+            ..self
+        }
+    }
+
+    // rounded down to 1, 2, 4, 8 or 16, only applied when both filters are linear
+    pub const fn with_anisotropy(self, anisotropy: u8) -> Self {
+        let anisotropy = match anisotropy {
+            0 => 1,
+            17.. => 16,
+            _ => 1 << (7 - anisotropy.leading_zeros()),
+        };
+        Self { anisotropy, ..self }
+    }
+
+    // anisotropic filtering is only valid with linear filtering throughout, pixel art samplers
+    // keep their sharp texels instead
+    pub const fn effective_anisotropy(&self) -> u8 {
+        match (self.filter, self.mipmap_filter) {
+            (FilterMode::Linear, FilterMode::Linear) => self.anisotropy,
+            _ => 1,
+        }
     }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Size {
     D2(Extent2d<u32>),
+    // layers of the same size, sampled by the vertices' layer
+    D2Array(Extent2d<u32>, u32),
+    D3(Extent3d<u32>),
+    // six square layers in the order +x, -x, +y, -y, +z, -z
+    Cube(u32),
 }
 
 impl Size {
     pub fn is_valid(&self) -> bool {
         match self {
             Size::D2(size) => size.is_valid(),
+            Size::D2Array(size, layers) => size.is_valid() && *layers > 0,
+            Size::D3(size) => size.is_valid(),
+            Size::Cube(side) => *side > 0,
         }
     }
 
     pub(crate) fn get_extent_dimension(&self) -> (wgpu::Extent3d, wgpu::TextureViewDimension) {
-        match self {
-            Self::D2(size) => (
-                wgpu::Extent3d {
-                    width: size.width,
-                    height: size.height,
-                    ..Default::default()
-                },
-                wgpu::TextureViewDimension::D2,
+        let (width, height, depth_or_array_layers, dimension) = match *self {
+            Self::D2(size) => (size.width, size.height, 1, wgpu::TextureViewDimension::D2),
+            Self::D2Array(size, layers) => (
+                size.width,
+                size.height,
+                layers,
+                wgpu::TextureViewDimension::D2Array,
             ),
-        }
+            Self::D3(size) => (
+                size.width,
+                size.height,
+                size.depth,
+                wgpu::TextureViewDimension::D3,
+            ),
+            Self::Cube(side) => (side, side, 6, wgpu::TextureViewDimension::Cube),
+        };
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers,
+        };
+        (size, dimension)
     }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Format {
     Rgba8Unorm,
+    Rgba8UnormSrgb,
+    Bgra8Unorm,
+    Bgra8UnormSrgb,
+    R8Unorm,
+    Rg8Unorm,
+    Rgba16Float,
+    R32Float,
+    Depth32Float,
+}
+
+impl Format {
+    // bytes per pixel
+    pub const fn pixel_size(&self) -> u32 {
+        match self {
+            Self::R8Unorm => 1,
+            Self::Rg8Unorm => 2,
+            Self::Rgba8Unorm
+            | Self::Rgba8UnormSrgb
+            | Self::Bgra8Unorm
+            | Self::Bgra8UnormSrgb
+            | Self::R32Float
+            | Self::Depth32Float => 4,
+            Self::Rgba16Float => 8,
+        }
+    }
+
+    pub const fn is_srgb(&self) -> bool {
+        matches!(self, Self::Rgba8UnormSrgb | Self::Bgra8UnormSrgb)
+    }
+
+    // depth textures can be drawn into, but not written to
+    pub const fn is_depth(&self) -> bool {
+        matches!(self, Self::Depth32Float)
+    }
+
+    // renderers sample with filtering, which 32 bit float formats don't support
+    pub const fn is_filterable(&self) -> bool {
+        !matches!(self, Self::R32Float | Self::Depth32Float)
+    }
+
+    // mip levels are only generated for 8 bit channels
+    const fn is_mipmappable(&self) -> bool {
+        matches!(
+            self,
+            Self::Rgba8Unorm
+                | Self::Rgba8UnormSrgb
+                | Self::Bgra8Unorm
+                | Self::Bgra8UnormSrgb
+                | Self::R8Unorm
+                | Self::Rg8Unorm
+        )
+    }
 }
 
 impl From<Format> for wgpu::TextureFormat {
     fn from(value: Format) -> Self {
         match value {
             Format::Rgba8Unorm => Self::Rgba8Unorm,
+            Format::Rgba8UnormSrgb => Self::Rgba8UnormSrgb,
+            Format::Bgra8Unorm => Self::Bgra8Unorm,
+            Format::Bgra8UnormSrgb => Self::Bgra8UnormSrgb,
+            Format::R8Unorm => Self::R8Unorm,
+            Format::Rg8Unorm => Self::Rg8Unorm,
+            Format::Rgba16Float => Self::Rgba16Float,
+            Format::R32Float => Self::R32Float,
+            Format::Depth32Float => Self::Depth32Float,
         }
     }
 }
@@ -82,6 +204,10 @@ impl From<AddressMode> for wgpu::AddressMode {
 pub struct Sampler {
     pub filter: FilterMode,
     pub address: AddressMode,
+    pub mipmap_filter: FilterMode,
+    pub lod_min: u32,
+    pub lod_max: u32,
+    pub anisotropy: u8,
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -89,6 +215,8 @@ pub enum Error {
     PixelsInvalid,
     OriginInvalid,
     SizeInvalid,
+    FormatUnwritable,
+    MipmapsUnsupported,
 }
 
 #[derive(Debug)]
@@ -98,6 +226,9 @@ pub struct Texture {
     format: Format,
     sampler: Option<Sampler>,
     handle: Rc<wgpu::Texture>,
+    mip_level_count: u32,
+    // the full size pixels every mip level is generated from, `None` without mipmaps
+    mip_source: Option<RefCell<Vec<u8>>>,
 }
 
 impl Texture {
@@ -108,53 +239,81 @@ impl Texture {
         format: Format,
         sampler: Option<Sampler>,
         pixels: Option<&[u8]>,
+        mipmaps: bool,
+    ) -> Result<Self, Error> {
+        let usage = wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::TEXTURE_BINDING;
+        let texture = Self::with_usage(device, queue, size, format, sampler, usage, mipmaps)?;
+        if let Some(pixels) = pixels {
+            texture.write(Offset3d::default(), texture.size(), pixels)?;
+        }
+        Ok(texture)
+    }
+
+    // 3d textures are never mipmapped
+    pub(super) fn with_usage(
+        device: &wgpu::Device,
+        queue: Rc<wgpu::Queue>,
+        size: Size,
+        format: Format,
+        sampler: Option<Sampler>,
+        usage: wgpu::TextureUsages,
+        mipmaps: bool,
     ) -> Result<Self, Error> {
         if !size.is_valid() {
             return Err(Error::SizeInvalid);
         }
-        let (texture_size, format_size, handle) = {
-            let (size, dimension) = size.get_extent_dimension();
-            let format: wgpu::TextureFormat = format.into();
-            let format_size = format.describe().block_size;
-            let usage = wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::TEXTURE_BINDING;
-            (
-                size,
-                format_size,
-                Rc::new(device.create_texture(&wgpu::TextureDescriptor {
-                    label: None,
-                    size,
-                    mip_level_count: 1,
-                    sample_count: 1,
-                    dimension: dimension.compatible_texture_dimension(),
-                    format,
-                    usage,
-                    view_formats: &[],
-                })),
-            )
-        };
-        if let Some(pixels) = pixels {
-            let origin = Offset3d::default();
-            let size = texture_size.into();
-            Self::write_texture(&queue, &handle, size, format_size, origin, size, pixels)?;
+        if mipmaps && !format.is_mipmappable() {
+            return Err(Error::MipmapsUnsupported);
         }
+        let (texture_size, dimension) = size.get_extent_dimension();
+        let mip_level_count = match (mipmaps, size) {
+            (false, _) | (true, Size::D3(_)) => 1,
+            (true, _) => texture_size.max_mips(wgpu::TextureDimension::D2),
+        };
+        let handle = Rc::new(device.create_texture(&wgpu::TextureDescriptor {
+            label: None,
+            size: texture_size,
+            mip_level_count,
+            sample_count: 1,
+            dimension: dimension.compatible_texture_dimension(),
+            format: format.into(),
+            usage,
+            view_formats: &[],
+        }));
+        let mip_source = (mip_level_count > 1).then(|| {
+            let texture_size = Extent3d::from(texture_size);
+            let source_size = texture_size.width * texture_size.height * texture_size.depth;
+            RefCell::new(vec![0; (format.pixel_size() * source_size) as usize])
+        });
         Ok(Self {
             queue,
             size,
             format,
             handle,
             sampler,
+            mip_level_count,
+            mip_source,
         })
     }
 
+    // the depth is the layer count of array and cube textures
     pub fn size(&self) -> Extent3d<u32> {
         let (size, _) = self.size.get_extent_dimension();
         size.into()
     }
 
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
     pub fn sampler(&self) -> Option<Sampler> {
         self.sampler
     }
 
+    pub fn mip_level_count(&self) -> u32 {
+        self.mip_level_count
+    }
+
     pub(crate) fn view_dimension(&self) -> wgpu::TextureViewDimension {
         let (_, view_dimension) = self.size.get_extent_dimension();
         view_dimension
@@ -164,38 +323,20 @@ impl Texture {
         &self.handle
     }
 
+    // writes into the full size level, regenerating the mip levels of every layer written to
     pub fn write(
         &self,
         origin: Offset3d<u32>,
         size: Extent3d<u32>,
         pixels: &[u8],
     ) -> Result<(), Error> {
-        let texture_size = self.size();
-        let format_size = wgpu::TextureFormat::from(self.format).describe().block_size;
-        Self::write_texture(
-            &self.queue,
-            &self.handle,
-            texture_size,
-            format_size,
-            origin,
-            size,
-            pixels,
-        )
-    }
-
-    #[inline]
-    fn write_texture(
-        queue: &wgpu::Queue,
-        texture: &wgpu::Texture,
-        texture_size: Extent3d<u32>,
-        format_size: u8,
-        origin: Offset3d<u32>,
-        size: Extent3d<u32>,
-        pixels: &[u8],
-    ) -> Result<(), Error> {
+        if self.format.is_depth() {
+            return Err(Error::FormatUnwritable);
+        }
         if !size.is_valid() {
             return Err(Error::SizeInvalid);
         };
+        let texture_size = self.size();
         let origin_size = size + origin;
         if origin_size.width > texture_size.width
             || origin_size.height > texture_size.height
@@ -203,24 +344,117 @@ impl Texture {
         {
             return Err(Error::OriginInvalid);
         }
-        if pixels.len() < (format_size as u32 * size.width * size.height * size.depth) as usize {
+        let format_size = self.format.pixel_size();
+        let pixel_count = size.width * size.height * size.depth;
+        if pixels.len() < (format_size * pixel_count) as usize {
             return Err(Error::PixelsInvalid);
         }
-        queue.write_texture(
+        self.write_level(0, origin, size, pixels);
+
+        let Some(mip_source) = self.mip_source.as_ref() else { return Ok(()); };
+        let mut mip_source = mip_source.borrow_mut();
+        let row_size = (format_size * size.width) as usize;
+        for z in 0..size.depth {
+            for y in 0..size.height {
+                let source = ((z * size.height + y) * size.width * format_size) as usize;
+                let texel = (origin.z + z) * texture_size.height + origin.y + y;
+                let target = ((texel * texture_size.width + origin.x) * format_size) as usize;
+                mip_source[target..target + row_size]
+                    .copy_from_slice(&pixels[source..source + row_size]);
+            }
+        }
+        let layer_size = (format_size * texture_size.width * texture_size.height) as usize;
+        for layer in origin.z..origin_size.depth {
+            let start = layer as usize * layer_size;
+            let mut level_pixels = mip_source[start..start + layer_size].to_vec();
+            let mut level_size = Extent2d::new(texture_size.width, texture_size.height);
+            for level in 1..self.mip_level_count {
+                (level_pixels, level_size) = downsample(&level_pixels, level_size, self.format);
+                let origin = Offset3d::new(0, 0, layer);
+                let size = Extent3d::new(level_size.width, level_size.height, 1);
+                self.write_level(level, origin, size, &level_pixels);
+            }
+        }
+        Ok(())
+    }
+
+    // writes a whole layer, or a depth slice of 3d textures
+    pub fn write_layer(&self, layer: u32, pixels: &[u8]) -> Result<(), Error> {
+        let texture_size = self.size();
+        let size = Extent3d::new(texture_size.width, texture_size.height, 1);
+        self.write(Offset3d::new(0, 0, layer), size, pixels)
+    }
+
+    #[inline]
+    fn write_level(&self, level: u32, origin: Offset3d<u32>, size: Extent3d<u32>, pixels: &[u8]) {
+        let format_size = self.format.pixel_size();
+        self.queue.write_texture(
             wgpu::ImageCopyTexture {
-                texture: &texture,
-                mip_level: 0,
+                texture: &self.handle,
+                mip_level: level,
                 origin: origin.into(),
                 aspect: wgpu::TextureAspect::All,
             },
             pixels,
             wgpu::ImageDataLayout {
                 offset: 0,
-                bytes_per_row: NonZeroU32::new(format_size as u32 * size.width),
+                bytes_per_row: NonZeroU32::new(format_size * size.width),
                 rows_per_image: NonZeroU32::new(size.height),
             },
             size.into(),
         );
-        Ok(())
     }
 }
+
+// halves an image with 8 bit channels by averaging blocks of 2x2 pixels, repeating the last row
+// or column of odd sizes, so layers never bleed into each other
+fn downsample(pixels: &[u8], size: Extent2d<u32>, format: Format) -> (Vec<u8>, Extent2d<u32>) {
+    let srgb = format.is_srgb();
+    let channels = format.pixel_size();
+    let to_linear = |value: u8| {
+        let value = value as f32 / 255.0;
+        match srgb {
+            true if value <= 0.04045 => value / 12.92,
+            true => ((value + 0.055) / 1.055).powf(2.4),
+            false => value,
+        }
+    };
+    let from_linear = |value: f32| {
+        let value = match srgb {
+            true if value <= 0.0031308 => value * 12.92,
+            true => 1.055 * value.powf(1.0 / 2.4) - 0.055,
+            false => value,
+        };
+        (value * 255.0).round().clamp(0.0, 255.0) as u8
+    };
+    let half_size = Extent2d::new((size.width / 2).max(1), (size.height / 2).max(1));
+    let half_pixel_count = half_size.width * half_size.height;
+    let mut half_pixels = Vec::with_capacity((channels * half_pixel_count) as usize);
+    for y in 0..half_size.height {
+        for x in 0..half_size.width {
+            let mut sum = [0.0f32; 4];
+            let sum = &mut sum[..channels as usize];
+            for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                let source_x = (2 * x + dx).min(size.width - 1);
+                let source_y = (2 * y + dy).min(size.height - 1);
+                let offset = (channels * (source_y * size.width + source_x)) as usize;
+                for (channel, sum) in sum.iter_mut().enumerate() {
+                    let value = pixels[offset + channel];
+                    // alpha is always linear
+                    *sum += match channel {
+                        3 => value as f32 / 255.0,
+                        _ => to_linear(value),
+                    };
+                }
+            }
+            for (channel, sum) in sum.iter().enumerate() {
+                let value = sum / 4.0;
+                half_pixels.push(match channel {
+                    3 => (value * 255.0).round() as u8,
+                    _ => from_linear(value),
+                });
+            }
+        }
+    }
+    (half_pixels, half_size)
+}
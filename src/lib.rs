@@ -0,0 +1,14 @@
+mod controller;
+pub mod game;
+pub mod graphics;
+mod input;
+mod performance;
+mod random;
+mod types;
+pub mod voxel;
+
+pub use controller::Controller;
+pub use game::Game;
+pub use random::Random;
+pub use types::*;
+pub use voxel::Voxel;
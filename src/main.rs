@@ -1,15 +1,4 @@
-mod controller;
-mod game;
-mod graphics;
-mod input;
-mod performance;
-mod types;
-mod voxel;
-
-pub use controller::Controller;
-pub use game::Game;
-pub use types::*;
-pub use voxel::Voxel;
+use ndrcraft::{game, graphics, voxel, Extent2d, Extent3d, Game};
 
 fn main() {
     env_logger::builder()
@@ -21,7 +10,12 @@ fn main() {
     let game = Game::new(game::Descriptor {
         window: game::WindowMode::Windowed(Extent2d::new(1424, 720)),
         vsync: false,
-        world_size: Extent3d::new(100, 12, 100),
+        sample_count: 4,
+        graphics: graphics::Descriptor::default(),
+        world_bounds: voxel::Region::centered(Extent3d::new(100, 12, 100)),
+        world_generator: voxel::Generator::Waves,
+        world_seed: 0,
+        screenshot_scale: 1,
     })
     .expect("valid game");
     game.run().expect("valid game loop");
@@ -0,0 +1,37 @@
+/// A small seeded generator (splitmix64), so simulations are reproducible.
+#[derive(Clone, Debug)]
+pub struct Random {
+    state: u64,
+}
+
+impl Random {
+    pub const fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut value = self.state;
+        value = (value ^ (value >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        value = (value ^ (value >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        value ^ (value >> 31)
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    /// Returns a value in `0..bound`, or 0 if `bound` is 0.
+    pub fn range(&mut self, bound: u32) -> u32 {
+        if bound == 0 {
+            return 0;
+        }
+        ((self.next_u32() as u64 * bound as u64) >> 32) as u32
+    }
+}
+
+impl Default for Random {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
@@ -1,6 +1,6 @@
 pub use cgmath::*;
 
-use std::{mem, ops, slice};
+use std::{hash, mem, ops, slice};
 use winit::dpi::{PhysicalPosition, PhysicalSize};
 
 #[macro_export(local_inner_macros)]
@@ -80,6 +80,14 @@ macro_rules! define_vector_struct {
             }
         }
 
+        impl<T: Unit + Eq> Eq for $t<T> {}
+
+        impl<T: Unit + hash::Hash> hash::Hash for $t<T> {
+            fn hash<H: hash::Hasher>(&self, state: &mut H) {
+                $(self.$m.hash(state);)*
+            }
+        }
+
         impl_ops_self!(Add, add, +, $t{$($m),*});
         impl_ops_self!(Sub, sub, -, $t{$($m),*});
         impl_ops_scalar!(Mul<T>, mul, *, $t{$($m),*});
@@ -1,23 +1,81 @@
+mod edit;
+mod events;
+mod export;
+mod file;
+mod fluid;
+mod generator;
+mod history;
+mod map;
+mod meshing;
+mod nbt;
+mod octree;
+mod query;
+mod region;
+mod schematic;
+mod sponge;
+mod stats;
+mod tick;
+
+pub use events::{EventReceiver, WorldEvent};
+pub use export::{ExportError, ExportFormat};
+pub use file::FileError;
+pub use fluid::Fluid;
+pub use generator::Generator;
+pub use meshing::MeshData;
+pub use octree::{Octree, RayHit};
+pub use query::{RegionIterator, SurfaceIterator};
+pub use region::Region;
+pub use schematic::{PasteOptions, Rotation, Schematic};
+pub use sponge::{BlockMapping, SpongeImport};
+pub use stats::{FaceMismatch, WorldStats};
+pub use tick::{Falling, Growing, Spreading, TickBehaviour};
+
 use crate::{
     graphics::{
         self,
-        mesh::{self, Mesh},
+        mesh::Mesh,
         texture::{self, Texture},
     },
-    impl_from_error, Color, Extent2d, Extent3d, Offset3d, Vector2, Vector3,
+    impl_from_error, Color, Extent2d, Extent3d, Offset3d, Random, Vector2, Vector3,
 };
 use bitflags::bitflags;
+use events::Events;
+use history::History;
+use std::{
+    collections::{BTreeMap, HashSet},
+    mem,
+    rc::Rc,
+};
 
 #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub enum Voxel {
     #[default]
     Void,
     Tile(u32),
+    Fluid(Fluid),
 }
 
-#[derive(Clone, Copy, Debug)]
+impl Voxel {
+    // whether `face` of this voxel should be meshed when `other` is on the other side, the sides of
+    // a fluid show above lower fluids next to it
+    fn is_face_visible(&self, face: Face, other: Voxel) -> bool {
+        match (self, other) {
+            (Self::Void, _) => false,
+            (Self::Tile(_), Self::Tile(_)) => false,
+            (Self::Tile(_), _) => true,
+            (Self::Fluid(_), Self::Void) => true,
+            (Self::Fluid(fluid), Self::Fluid(other)) => match face {
+                Face::PosY | Face::NegY => false,
+                _ => other.level < fluid.level,
+            },
+            (Self::Fluid(_), _) => false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 #[repr(u8)]
-enum Face {
+pub enum Face {
     PosX,
     NegX,
     PosY,
@@ -27,6 +85,15 @@ enum Face {
 }
 
 impl Face {
+    pub const ALL: [Self; 6] = [
+        Self::PosX,
+        Self::NegX,
+        Self::PosY,
+        Self::NegY,
+        Self::PosZ,
+        Self::NegZ,
+    ];
+
     fn from_index(index: u8) -> Self {
         match index {
             0 => Self::PosX,
@@ -49,7 +116,7 @@ impl Face {
         [0, 1, 2, 1, 3, 2]
     }
 
-    fn opposite(&self) -> Self {
+    pub fn opposite(&self) -> Self {
         match self {
             Self::PosX => Self::NegX,
             Self::NegX => Self::PosX,
@@ -101,30 +168,26 @@ impl Face {
         }
     }
 
-    fn get_vertex_uvs(&self) -> [Vector2<f32>; Self::VERTEX_COUNT] {
+    // every face covers a whole section, see `get_texture_section`
+    fn get_vertex_uvs() -> [Vector2<f32>; Self::VERTEX_COUNT] {
+        [
+            Vector2::new(0.0, 1.0),
+            Vector2::new(0.0, 0.0),
+            Vector2::new(1.0, 1.0),
+            Vector2::new(1.0, 0.0),
+        ]
+    }
+
+    // tile textures stack the top, side and bottom sections from top to bottom
+    fn get_texture_section(&self) -> u32 {
         match self {
-            Face::PosX | Face::NegX | Face::PosZ | Face::NegZ => [
-                Vector2::new(0.0, 0.667),
-                Vector2::new(0.0, 0.333),
-                Vector2::new(1.0, 0.667),
-                Vector2::new(1.0, 0.333),
-            ],
-            Face::PosY => [
-                Vector2::new(0.0, 0.333),
-                Vector2::new(0.0, 0.0),
-                Vector2::new(1.0, 0.333),
-                Vector2::new(1.0, 0.0),
-            ],
-            Face::NegY => [
-                Vector2::new(0.0, 1.0),
-                Vector2::new(0.0, 0.667),
-                Vector2::new(1.0, 1.0),
-                Vector2::new(1.0, 0.667),
-            ],
+            Face::PosY => 0,
+            Face::PosX | Face::NegX | Face::PosZ | Face::NegZ => 1,
+            Face::NegY => 2,
         }
     }
 
-    fn get_voxel_normal(&self) -> Offset3d<i32> {
+    pub fn get_voxel_normal(&self) -> Offset3d<i32> {
         match *self {
             Face::PosX => Offset3d::new(1, 0, 0),
             Face::NegX => Offset3d::new(-1, 0, 0),
@@ -138,7 +201,7 @@ impl Face {
 
 bitflags! {
     #[repr(transparent)]
-    struct Faces: u8 {
+    pub struct Faces: u8 {
         const POS_X = (1 << 0);
         const NEG_X = (1 << 1);
         const POS_Y = (1 << 2);
@@ -148,6 +211,14 @@ bitflags! {
     }
 }
 
+impl Faces {
+    pub fn iter(self) -> impl Iterator<Item = Face> {
+        Face::ALL
+            .into_iter()
+            .filter(move |face| self.contains(Self::from(*face)))
+    }
+}
+
 impl From<Face> for Faces {
     fn from(value: Face) -> Self {
         match value {
@@ -176,6 +247,17 @@ impl Default for VoxelData {
     }
 }
 
+/// How positions outside the world are treated when culling faces on its border.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Boundary {
+    /// Border faces are hidden.
+    Solid,
+    #[default]
+    Void,
+    /// Positions wrap around to the opposite side of the world.
+    Wrap,
+}
+
 #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
 pub enum TextureLayout {
     #[default]
@@ -186,6 +268,9 @@ pub enum TextureLayout {
 pub enum WorldError {
     PositionInvalid(Offset3d<i32>),
     TileIndexInvalid(u32),
+    FluidLevelInvalid(u8),
+    TileCountUnsupported(u32),
+    TransactionOpen,
     DataInvalid,
     Graphics(graphics::Error),
     Texture(texture::Error),
@@ -199,66 +284,149 @@ pub struct World {
     origin_offset: Offset3d<i32>,
     voxel_data: Vec<VoxelData>,
     max_tiles: u32,
-    mesh: Mesh,
-    texture: Texture,
+    boundary: Boundary,
+    revision: u64,
+    tick_count: u64,
+    random: Random,
+    random_tick_count: u32,
+    scheduled_ticks: BTreeMap<u64, Vec<Offset3d<i32>>>,
+    neighbour_updates: Vec<(Offset3d<i32>, Offset3d<i32>)>,
+    queued_updates: HashSet<(Offset3d<i32>, Offset3d<i32>)>,
+    tile_behaviours: Vec<Option<Rc<dyn TickBehaviour>>>,
+    tile_pixels: Vec<Option<Vec<u8>>>,
+    history: History,
+    events: Events,
+    mesh: Option<Mesh>,
+    texture: Option<Texture>,
 }
 
 impl World {
     const TEXTURE_SIZE: Extent2d<u32> = Extent2d::new(8, 24);
+    const TEXTURE_SECTIONS: u32 = 3;
+    const SECTION_SIZE: Extent2d<u32> = Extent2d::new(8, 8);
+    const FLUID_COLOR: Color<f32> = Color::new(1.0, 1.0, 1.0, 0.75);
 
     pub(crate) fn new(
         graphics: &graphics::Context,
-        size: Extent3d<u32>,
+        bounds: Region,
         max_tiles: u32,
     ) -> Result<Self, WorldError> {
-        let half_size = Extent3d::new(
-            size.width as i64 / 2,
-            size.height as i64 / 2,
-            size.depth as i64 / 2,
-        );
-        let origin_offset = Offset3d::new(
-            -half_size.width as i32,
-            -half_size.height as i32,
-            -half_size.depth as i32,
-        );
-        let voxel_data =
-            vec![VoxelData::default(); (size.width * size.height * size.depth) as usize];
-        let mesh = graphics.create_mesh(&[], &[]);
-        let texture_size = texture::Size::D2(Extent2d::new(
-            Self::TEXTURE_SIZE.width * max_tiles,
-            Self::TEXTURE_SIZE.height,
-        ));
+        // one layer per section of every tile, so merged faces can repeat their section
+        let layers = max_tiles
+            .checked_mul(Self::TEXTURE_SECTIONS)
+            .filter(|&layers| layers <= graphics.device.limits().max_texture_array_layers)
+            .ok_or(WorldError::TileCountUnsupported(max_tiles))?;
+        let mut world = Self::new_headless(bounds, max_tiles);
+        let texture_size = texture::Size::D2Array(Self::SECTION_SIZE, layers);
+        let sampler =
+            texture::Sampler::new(texture::FilterMode::Nearest, texture::AddressMode::Repeat)
+                .with_mipmap_filter(texture::FilterMode::Linear);
         let texture = graphics
             .create_texture(
                 texture_size,
-                texture::Format::Rgba8Unorm,
-                Some(texture::Sampler::new(
-                    texture::FilterMode::Nearest,
-                    texture::AddressMode::ClampToEdge,
-                )),
+                texture::Format::Rgba8UnormSrgb,
+                Some(sampler),
                 None,
+                true,
             )
             .map_err(|error| WorldError::from(error))?;
-        Ok(Self {
-            size,
-            origin_offset,
+        world.mesh = Some(graphics.create_mesh(&[], &[]));
+        world.texture = Some(texture);
+        Ok(world)
+    }
+
+    // a world without a mesh or texture, for tools that never render
+    pub fn new_headless(bounds: Region, max_tiles: u32) -> Self {
+        Self {
+            size: bounds.size(),
+            origin_offset: bounds.min,
             max_tiles,
-            voxel_data,
-            mesh,
-            texture,
-        })
+            voxel_data: vec![VoxelData::default(); bounds.volume()],
+            boundary: Boundary::default(),
+            revision: 0,
+            tick_count: 0,
+            random: Random::default(),
+            random_tick_count: 3,
+            scheduled_ticks: BTreeMap::new(),
+            neighbour_updates: Vec::new(),
+            queued_updates: HashSet::new(),
+            tile_behaviours: vec![None; max_tiles as usize],
+            tile_pixels: vec![None; max_tiles as usize],
+            history: History::new(),
+            events: Events::default(),
+            mesh: None,
+            texture: None,
+        }
     }
 
     pub fn size(&self) -> Extent3d<u32> {
         self.size
     }
 
-    pub fn mesh(&self) -> &Mesh {
-        &self.mesh
+    pub fn origin(&self) -> Offset3d<i32> {
+        self.origin_offset
+    }
+
+    pub fn bounds(&self) -> Region {
+        Region::from_size(self.origin_offset, self.size)
+    }
+
+    pub fn max_tiles(&self) -> u32 {
+        self.max_tiles
     }
 
-    pub fn texture(&self) -> &Texture {
-        &self.texture
+    pub fn boundary(&self) -> Boundary {
+        self.boundary
+    }
+
+    pub fn set_boundary(&mut self, boundary: Boundary) {
+        if self.boundary != boundary {
+            self.boundary = boundary;
+            self.refresh_border_faces();
+        }
+    }
+
+    /// Moves the world to `bounds`, keeping every voxel inside both the old and new bounds. The
+    /// edit history is cleared since it may refer to positions that no longer exist.
+    pub fn resize(&mut self, bounds: Region) {
+        let old_bounds = self.bounds();
+        if old_bounds == bounds {
+            return;
+        }
+        let old_data = mem::replace(
+            &mut self.voxel_data,
+            vec![VoxelData::default(); bounds.volume()],
+        );
+        self.size = bounds.size();
+        self.origin_offset = bounds.min;
+        if let Some(kept) = old_bounds.intersection(&bounds) {
+            let old_size = old_bounds.size();
+            for position in kept.positions() {
+                let offset = position - old_bounds.min;
+                let old_index = (offset.z as usize * old_size.height as usize + offset.y as usize)
+                    * old_size.width as usize
+                    + offset.x as usize;
+                let index = unsafe { self.get_voxel_index_unchecked(position) };
+                self.voxel_data[index].voxel = old_data[old_index].voxel;
+            }
+        }
+        self.revision += 1;
+        self.clear_history();
+        self.refresh_faces(bounds);
+        self.publish_region_changed(bounds);
+    }
+
+    /// Grows the world just enough to contain `region`.
+    pub fn expand(&mut self, region: Region) {
+        self.resize(self.bounds().union(&region));
+    }
+
+    pub fn mesh(&self) -> Option<&Mesh> {
+        self.mesh.as_ref()
+    }
+
+    pub fn texture(&self) -> Option<&Texture> {
+        self.texture.as_ref()
     }
 
     pub fn get_voxel(&self, position: Offset3d<i32>) -> Option<&Voxel> {
@@ -272,15 +440,10 @@ impl World {
         voxel: Voxel,
     ) -> Result<Voxel, WorldError> {
         let Some(target_index) = self.get_voxel_index(position) else { return Err(WorldError::PositionInvalid(position)); };
-        if let Voxel::Tile(tile_index) = voxel {
-            if tile_index >= self.max_tiles {
-                return Err(WorldError::TileIndexInvalid(tile_index));
-            }
-        }
+        self.validate_voxel(voxel)?;
 
         // set voxel
-        let old_voxel = self.voxel_data[target_index].voxel;
-        self.voxel_data[target_index].voxel = voxel;
+        let old_voxel = self.store_voxel(target_index, position, voxel);
 
         // set faces
         // iterate all target faces
@@ -289,37 +452,33 @@ impl World {
 
             // get voxel adjacent to face
             let other_position = position + face.get_voxel_normal();
-            let Some(other_index) = self.get_voxel_index(other_position) else {
-                self.voxel_data[target_index].faces.insert(Faces::from(face));
+            let Some(other_index) = self.get_neighbour_index(other_position) else {
+                let other_voxel = self.get_neighbour_voxel(other_position);
+                self.voxel_data[target_index]
+                    .faces
+                    .set(Faces::from(face), voxel.is_face_visible(face, other_voxel));
                 continue;
             };
-            let target_voxel = voxel;
             let other_voxel = self.voxel_data[other_index].voxel;
-            match (target_voxel, other_voxel) {
-                (Voxel::Void, Voxel::Void) => {}
-                (Voxel::Void, Voxel::Tile(_)) => {
-                    self.voxel_data[other_index]
-                        .faces
-                        .insert(Faces::from(face.opposite()));
-                }
-                (Voxel::Tile(_), Voxel::Void) => {
-                    self.voxel_data[target_index]
-                        .faces
-                        .insert(Faces::from(face));
-                }
-                (Voxel::Tile(_), Voxel::Tile(_)) => {
-                    self.voxel_data[other_index]
-                        .faces
-                        .remove(Faces::from(face.opposite()));
-                }
-            }
+            self.voxel_data[target_index]
+                .faces
+                .set(Faces::from(face), voxel.is_face_visible(face, other_voxel));
+            self.voxel_data[other_index].faces.set(
+                Faces::from(face.opposite()),
+                other_voxel.is_face_visible(face.opposite(), voxel),
+            );
+        }
+
+        if old_voxel != voxel {
+            self.notify_neighbours(position);
+            self.publish_voxel_changed(position, old_voxel, voxel);
         }
 
         Ok(old_voxel)
     }
 
     pub fn set_voxel_texture(
-        &self,
+        &mut self,
         tile_index: u32,
         layout: TextureLayout,
         pixels: &[u8],
@@ -336,70 +495,143 @@ impl World {
             return Err(WorldError::DataInvalid);
         }
 
-        let origin = Offset3d::new(Self::TEXTURE_SIZE.width * tile_index, 0, 0);
-        let size = Extent3d::new(Self::TEXTURE_SIZE.width, Self::TEXTURE_SIZE.height, 1);
-        self.texture.write(origin, size, pixels)?;
+        self.tile_pixels[tile_index as usize] = Some(pixels[..required_size].to_owned());
+
+        let Some(texture) = self.texture.as_ref() else { return Ok(()); };
+        let section_size = required_size / Self::TEXTURE_SECTIONS as usize;
+        for (section, section_pixels) in pixels[..required_size]
+            .chunks_exact(section_size)
+            .enumerate()
+        {
+            texture.write_layer(
+                tile_index * Self::TEXTURE_SECTIONS + section as u32,
+                section_pixels,
+            )?;
+        }
         Ok(())
     }
 
+    // submesh 0 holds opaque tiles, submesh 1 holds translucent fluids
     pub fn generate_mesh(&mut self) {
-        self.mesh.vertices.clear();
-        self.mesh.submeshes.clear();
-        let mut vertices = Vec::new();
-        let mut submesh = mesh::Submesh::new(&[]);
-        for (voxel_position, voxel_data) in self.iter() {
-            match voxel_data.voxel {
-                Voxel::Void => continue,
-                Voxel::Tile(tile_index) => {
-                    let world_position = Vector3::new(
-                        voxel_position.x as f32,
-                        voxel_position.y as f32,
-                        voxel_position.z as f32,
-                    );
-                    let face_indices = Face::get_indices();
-                    for face_index in 0..Face::CUBE_FACE_COUNT {
-                        let face = Face::from_index(face_index);
-                        if !voxel_data.faces.contains(Faces::from(face)) {
-                            continue;
-                        }
-                        let vertex_count = vertices.len() as u32;
-                        let indices = face_indices.map(|index| vertex_count + index);
-                        let face_vertex_positions = face.get_vertex_positions();
-                        let face_vertex_uvs = face.get_vertex_uvs();
-                        vertices.extend(
-                            face_vertex_positions
-                                .iter()
-                                .zip(face_vertex_uvs.iter())
-                                .map(|(position, uv)| {
-                                    let position = *position + world_position;
-                                    let color = Color::white();
-                                    let uv = Vector2::new(
-                                        (tile_index as f32 + uv.x) / self.max_tiles as f32,
-                                        uv.y,
-                                    );
-                                    mesh::Vertex::new(position, color, uv)
-                                }),
-                        );
-                        submesh.indices.extend(indices.iter());
-                    }
+        if self.mesh.is_none() {
+            return;
+        }
+        let mesh_data = self.build_mesh(self.bounds(), false);
+        if let Some(mesh) = self.mesh.as_mut() {
+            mesh.vertices.clear();
+            mesh.submeshes.clear();
+            mesh.vertices.extend_from_slice(&mesh_data.vertices);
+            mesh.submeshes.extend(mesh_data.submeshes);
+        }
+    }
+
+    fn validate_voxel(&self, voxel: Voxel) -> Result<(), WorldError> {
+        match voxel {
+            Voxel::Void => {}
+            Voxel::Tile(tile_index) => {
+                if tile_index >= self.max_tiles {
+                    return Err(WorldError::TileIndexInvalid(tile_index));
+                }
+            }
+            Voxel::Fluid(fluid) => {
+                if fluid.tile_index >= self.max_tiles {
+                    return Err(WorldError::TileIndexInvalid(fluid.tile_index));
+                }
+                if fluid.level == 0 || fluid.level > Fluid::MAX_LEVEL {
+                    return Err(WorldError::FluidLevelInvalid(fluid.level));
                 }
             }
         }
-        self.mesh.vertices.extend_from_slice(&vertices);
-        self.mesh.submeshes.extend(Some(submesh));
+        Ok(())
     }
 
+    // write a voxel and journal the change without touching any faces
     #[inline]
-    fn iter(&self) -> WorldIterator<'_> {
-        WorldIterator::new(self)
+    fn store_voxel(&mut self, index: usize, position: Offset3d<i32>, voxel: Voxel) -> Voxel {
+        let old_voxel = mem::replace(&mut self.voxel_data[index].voxel, voxel);
+        if old_voxel != voxel {
+            self.revision += 1;
+            self.history.record(position, old_voxel, voxel);
+        }
+        old_voxel
+    }
+
+    // recompute faces from scratch, used after writing voxel data directly
+    fn refresh_faces(&mut self, region: Region) {
+        // when wrapping, positions just outside the world are voxels on the opposite side
+        let bounds = match self.boundary {
+            Boundary::Wrap => self.bounds().expand(1),
+            _ => self.bounds(),
+        };
+        let Some(region) = region.intersection(&bounds) else { return; };
+        for position in region.positions() {
+            let Some(index) = self.get_neighbour_index(position) else { continue; };
+            let position = unsafe { self.get_voxel_position_unchecked(index) };
+            self.voxel_data[index].faces = self.compute_faces(position);
+        }
+    }
+
+    // the one voxel thick shell of the world, whose faces depend on the boundary
+    fn refresh_border_faces(&mut self) {
+        let Region { min, max } = self.bounds();
+        let slabs = [
+            (min, Offset3d::new(min.x, max.y, max.z)),
+            (Offset3d::new(max.x, min.y, min.z), max),
+            (min, Offset3d::new(max.x, min.y, max.z)),
+            (Offset3d::new(min.x, max.y, min.z), max),
+            (min, Offset3d::new(max.x, max.y, min.z)),
+            (Offset3d::new(min.x, min.y, max.z), max),
+        ];
+        for (a, b) in slabs {
+            self.refresh_faces(Region::new(a, b));
+        }
+    }
+
+    fn compute_faces(&self, position: Offset3d<i32>) -> Faces {
+        let Some(&voxel) = self.get_voxel(position) else { return Faces::empty(); };
+        let mut faces = Faces::empty();
+        for face_index in 0..Face::CUBE_FACE_COUNT {
+            let face = Face::from_index(face_index);
+            let other_voxel = self.get_neighbour_voxel(position + face.get_voxel_normal());
+            faces.set(Faces::from(face), voxel.is_face_visible(face, other_voxel));
+        }
+        faces
+    }
+
+    // the voxel a face looks at, outside the world this depends on the boundary
+    fn get_neighbour_voxel(&self, position: Offset3d<i32>) -> Voxel {
+        match (self.get_neighbour_index(position), self.boundary) {
+            (Some(index), _) => self.voxel_data[index].voxel,
+            // any tile hides the faces looking at it
+            (None, Boundary::Solid) => Voxel::Tile(0),
+            (None, _) => Voxel::Void,
+        }
+    }
+
+    #[inline]
+    fn get_neighbour_index(&self, position: Offset3d<i32>) -> Option<usize> {
+        match self.boundary {
+            Boundary::Wrap => {
+                let wrap = |value: i32, min: i32, size: u32| {
+                    (min as i64 + (value as i64 - min as i64).rem_euclid(size as i64)) as i32
+                };
+                let origin = self.origin_offset;
+                self.get_voxel_index(Offset3d::new(
+                    wrap(position.x, origin.x, self.size.width),
+                    wrap(position.y, origin.y, self.size.height),
+                    wrap(position.z, origin.z, self.size.depth),
+                ))
+            }
+            _ => self.get_voxel_index(position),
+        }
     }
 
     #[inline]
     fn get_voxel_index(&self, position: Offset3d<i32>) -> Option<usize> {
         let pos = Offset3d::new(
-            (position.x - self.origin_offset.x) as u32,
-            (position.y - self.origin_offset.y) as u32,
-            (position.z - self.origin_offset.z) as u32,
+            position.x.wrapping_sub(self.origin_offset.x) as u32,
+            position.y.wrapping_sub(self.origin_offset.y) as u32,
+            position.z.wrapping_sub(self.origin_offset.z) as u32,
         );
         if pos.x >= self.size.width || pos.y >= self.size.height || pos.z >= self.size.depth {
             return None;
@@ -407,15 +639,6 @@ impl World {
         unsafe { Some(self.get_voxel_index_unchecked(position)) }
     }
 
-    #[inline]
-    fn get_voxel_position(&self, index: usize) -> Option<Offset3d<i32>> {
-        if index >= self.voxel_data.len() {
-            None
-        } else {
-            unsafe { Some(self.get_voxel_position_unchecked(index)) }
-        }
-    }
-
     #[inline]
     unsafe fn get_voxel_index_unchecked(&self, position: Offset3d<i32>) -> usize {
         let (width, height) = {
@@ -435,19 +658,14 @@ impl World {
     #[inline]
     unsafe fn get_voxel_position_unchecked(&self, index: usize) -> Offset3d<i32> {
         let index = index as i64;
-        let (width, height, depth) = {
+        let (width, height) = {
             let size = self.size();
-            (size.width as i64, size.height as i64, size.depth as i64)
+            (size.width as i64, size.height as i64)
         };
-        let (origin_x, origin_y, origin_z) = (width / 2, height / 2, depth / 2);
         let x = index % width;
         let y = (index / width) % height;
         let z = index / (width * height);
-        Offset3d::new(
-            (x - origin_x) as i32,
-            (y - origin_y) as i32,
-            (z - origin_z) as i32,
-        )
+        self.origin_offset + Offset3d::new(x as i32, y as i32, z as i32)
     }
 
     #[inline]
@@ -456,24 +674,128 @@ impl World {
     }
 }
 
-struct WorldIterator<'a> {
-    index: usize,
-    world: &'a World,
+// an empty 8x8x8 world centred on the origin with 3 tiles, shared by the tests
+#[cfg(test)]
+pub(crate) fn new_test_world() -> World {
+    World::new_headless(Region::centered(Extent3d::new(8, 8, 8)), 3)
 }
 
-impl<'a> WorldIterator<'a> {
-    fn new(world: &'a World) -> Self {
-        Self { index: 0, world }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pos(x: i32, y: i32, z: i32) -> Offset3d<i32> {
+        Offset3d::new(x, y, z)
+    }
+
+    #[test]
+    fn resize_keeps_contents() {
+        let mut world = new_test_world();
+        world.set_voxel(pos(-4, -4, -4), Voxel::Tile(0)).unwrap();
+        world.set_voxel(pos(1, 2, 3), Voxel::Tile(1)).unwrap();
+        world.set_voxel(pos(3, 3, 3), Voxel::Tile(2)).unwrap();
+
+        let bounds = Region::new(pos(-2, 0, 1), pos(5, 9, 3));
+        world.resize(bounds);
+        assert_eq!(world.bounds(), bounds);
+        assert_eq!(world.size(), Extent3d::new(8, 10, 3));
+        assert_eq!(world.get_voxel(pos(-4, -4, -4)), None);
+        assert_eq!(world.get_voxel(pos(1, 2, 3)), Some(&Voxel::Tile(1)));
+        assert_eq!(world.get_voxel(pos(3, 3, 3)), Some(&Voxel::Tile(2)));
+        assert_eq!(world.get_voxel(pos(5, 9, 3)), Some(&Voxel::Void));
+
+        world.expand(Region::new(pos(-8, 0, 0), pos(-8, 0, 0)));
+        assert_eq!(world.bounds(), Region::new(pos(-8, 0, 0), pos(5, 9, 3)));
+        assert_eq!(world.get_voxel(pos(1, 2, 3)), Some(&Voxel::Tile(1)));
+        // already contained
+        world.expand(Region::new(pos(0, 1, 2), pos(1, 2, 3)));
+        assert_eq!(world.size(), Extent3d::new(14, 10, 4));
+        assert!(world.validate().is_empty());
     }
-}
 
-impl<'a> Iterator for WorldIterator<'a> {
-    type Item = (Offset3d<i32>, &'a VoxelData);
+    #[test]
+    fn resize_recomputes_border_faces() {
+        let mut world = new_test_world();
+        world.set_boundary(Boundary::Solid);
+        world.set_voxel(pos(3, 0, 0), Voxel::Tile(0)).unwrap();
+        world.set_voxel(pos(0, 0, 0), Voxel::Tile(0)).unwrap();
+        world.set_voxel(pos(1, 0, 0), Voxel::Tile(0)).unwrap();
+        assert!(!world
+            .get_faces(pos(3, 0, 0))
+            .unwrap()
+            .contains(Faces::POS_X));
+
+        // the old border is now open
+        world.expand(Region::new(pos(5, 0, 0), pos(5, 0, 0)));
+        assert!(world
+            .get_faces(pos(3, 0, 0))
+            .unwrap()
+            .contains(Faces::POS_X));
+
+        // and the new one is solid even where a visible neighbour was cut off
+        world.set_boundary(Boundary::Void);
+        world.resize(Region::new(pos(-4, -4, -4), pos(0, 3, 3)));
+        assert!(world
+            .get_faces(pos(0, 0, 0))
+            .unwrap()
+            .contains(Faces::POS_X));
+        world.set_boundary(Boundary::Solid);
+        assert!(!world
+            .get_faces(pos(0, 0, 0))
+            .unwrap()
+            .contains(Faces::POS_X));
+        assert!(world.validate().is_empty());
+    }
+
+    #[test]
+    fn boundary_faces() {
+        let mut world = new_test_world();
+        let Region { min, max } = world.bounds();
+        let corner = min;
+        world.set_voxel(corner, Voxel::Tile(0)).unwrap();
+        let outer = Faces::NEG_X | Faces::NEG_Y | Faces::NEG_Z;
+
+        assert_eq!(world.boundary(), Boundary::Void);
+        assert_eq!(world.get_faces(corner), Some(Faces::all()));
+        world.set_boundary(Boundary::Solid);
+        assert_eq!(world.get_faces(corner), Some(Faces::all() - outer));
+
+        // wrapped faces look at the opposite side, which is empty until filled
+        world.set_boundary(Boundary::Wrap);
+        assert_eq!(world.get_faces(corner), Some(Faces::all()));
+        let opposite = Offset3d::new(max.x, min.y, min.z);
+        world.set_voxel(opposite, Voxel::Tile(1)).unwrap();
+        assert_eq!(world.get_faces(corner), Some(Faces::all() - Faces::NEG_X));
+        assert!(!world.get_faces(opposite).unwrap().contains(Faces::POS_X));
+        assert!(world.validate().is_empty());
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
-        let position = self.world.get_voxel_position(self.index)?;
-        self.index += 1;
-        let voxel_data = self.world.get_voxel_data(position)?;
-        Some((position, voxel_data))
+    #[test]
+    fn neighbour_index() {
+        let mut world = World::new_headless(Region::new(pos(10, -3, 0), pos(13, 0, 1)), 3);
+        let min = pos(10, -3, 0);
+        let inside = world.get_voxel_index(min);
+        assert!(inside.is_some());
+        assert_eq!(world.get_neighbour_index(pos(14, -3, 0)), None);
+
+        for boundary in [Boundary::Void, Boundary::Solid] {
+            world.set_boundary(boundary);
+            assert_eq!(world.get_neighbour_index(min), inside);
+            assert_eq!(world.get_neighbour_index(pos(9, -3, 0)), None);
+        }
+
+        world.set_boundary(Boundary::Wrap);
+        assert_eq!(world.get_neighbour_index(pos(14, -3, 0)), inside);
+        assert_eq!(world.get_neighbour_index(pos(10, 1, 0)), inside);
+        assert_eq!(world.get_neighbour_index(pos(10, -3, -2)), inside);
+        assert_eq!(world.get_neighbour_index(pos(10 - 40, -3 + 8, 6)), inside);
+        assert_eq!(
+            world.get_neighbour_index(pos(9, 0, 1)),
+            world.get_voxel_index(pos(13, 0, 1))
+        );
+        // wrapping far positions doesn't overflow
+        assert!(world
+            .get_neighbour_index(pos(i32::MAX, i32::MIN, i32::MAX))
+            .is_some());
     }
 }
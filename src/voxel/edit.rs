@@ -0,0 +1,343 @@
+use super::{Region, Voxel, World, WorldError};
+use crate::Offset3d;
+
+impl World {
+    pub fn fill(&mut self, region: Region, voxel: Voxel) -> Result<usize, WorldError> {
+        self.edit_region(region, "fill", |_, _| Some(voxel))
+    }
+
+    // every voxel whose centre lies within `radius + 0.5` of the centre
+    pub fn fill_sphere(
+        &mut self,
+        center: Offset3d<i32>,
+        radius: u32,
+        voxel: Voxel,
+    ) -> Result<usize, WorldError> {
+        let radius = radius as i64;
+        let region = Region::new(
+            Offset3d::new(
+                clamp_i32(center.x as i64 - radius),
+                clamp_i32(center.y as i64 - radius),
+                clamp_i32(center.z as i64 - radius),
+            ),
+            Offset3d::new(
+                clamp_i32(center.x as i64 + radius),
+                clamp_i32(center.y as i64 + radius),
+                clamp_i32(center.z as i64 + radius),
+            ),
+        );
+        let limit = (radius as f64 + 0.5).powi(2);
+        self.edit_region(region, "fill sphere", |position, _| {
+            // squared in f64 as three squared i32 differences overflow an i64
+            let (x, y, z) = (
+                position.x as f64 - center.x as f64,
+                position.y as f64 - center.y as f64,
+                position.z as f64 - center.z as f64,
+            );
+            let distance = x * x + y * y + z * z;
+            (distance <= limit).then_some(voxel)
+        })
+    }
+
+    // an upright cylinder standing on `base`
+    pub fn fill_cylinder(
+        &mut self,
+        base: Offset3d<i32>,
+        radius: u32,
+        height: u32,
+        voxel: Voxel,
+    ) -> Result<usize, WorldError> {
+        if height == 0 {
+            return Ok(0);
+        }
+        let radius = radius as i64;
+        let region = Region::new(
+            Offset3d::new(
+                clamp_i32(base.x as i64 - radius),
+                base.y,
+                clamp_i32(base.z as i64 - radius),
+            ),
+            Offset3d::new(
+                clamp_i32(base.x as i64 + radius),
+                clamp_i32(base.y as i64 + height as i64 - 1),
+                clamp_i32(base.z as i64 + radius),
+            ),
+        );
+        let limit = (radius as f64 + 0.5).powi(2);
+        self.edit_region(region, "fill cylinder", |position, _| {
+            let (x, z) = (
+                position.x as f64 - base.x as f64,
+                position.z as f64 - base.z as f64,
+            );
+            let distance = x * x + z * z;
+            (distance <= limit).then_some(voxel)
+        })
+    }
+
+    pub fn replace(&mut self, region: Region, from: Voxel, to: Voxel) -> Result<usize, WorldError> {
+        self.edit_region(region, "replace", |_, voxel| (voxel == from).then_some(to))
+    }
+
+    // clears tiles strictly inside the region that are enclosed by other tiles on every side,
+    // leaving only a shell
+    pub fn hollow(&mut self, region: Region) -> Result<usize, WorldError> {
+        let Region { min, max } = region;
+        let interior = Region {
+            min: Offset3d::new(
+                min.x.saturating_add(1),
+                min.y.saturating_add(1),
+                min.z.saturating_add(1),
+            ),
+            max: Offset3d::new(
+                max.x.saturating_sub(1),
+                max.y.saturating_sub(1),
+                max.z.saturating_sub(1),
+            ),
+        };
+        let Some(region) = interior.intersection(&self.bounds()) else { return Ok(0); };
+        // decide up front so cleared voxels don't expose the ones behind them
+        let enclosed: Vec<_> = region
+            .positions()
+            .map(|position| match self.get_voxel_data(position) {
+                Some(voxel_data) => {
+                    matches!(voxel_data.voxel, Voxel::Tile(_)) && voxel_data.faces.is_empty()
+                }
+                None => false,
+            })
+            .collect();
+        let mut enclosed = enclosed.into_iter();
+        self.edit_region(region, "hollow", |_, _| {
+            enclosed.next().unwrap_or_default().then_some(Voxel::Void)
+        })
+    }
+
+    // the four vertical sides of the region
+    pub fn walls(&mut self, region: Region, voxel: Voxel) -> Result<usize, WorldError> {
+        let Region { min, max } = region;
+        self.edit_region(region, "walls", |position, _| {
+            let on_wall = position.x == min.x
+                || position.x == max.x
+                || position.z == min.z
+                || position.z == max.z;
+            on_wall.then_some(voxel)
+        })
+    }
+
+    // writes every voxel `edit` returns in one pass as a single transaction, then refreshes faces
+    // and notifies listeners once for the changed region plus a one voxel border
+    pub(super) fn edit_region<F>(
+        &mut self,
+        region: Region,
+        name: &str,
+        mut edit: F,
+    ) -> Result<usize, WorldError>
+    where
+        F: FnMut(Offset3d<i32>, Voxel) -> Option<Voxel>,
+    {
+        let Some(region) = region.intersection(&self.bounds()) else { return Ok(0); };
+        self.begin_transaction(name);
+        let mut changed = 0;
+        let mut changed_region: Option<Region> = None;
+        let mut result = Ok(());
+        for position in region.positions() {
+            let index = unsafe { self.get_voxel_index_unchecked(position) };
+            let Some(voxel) = edit(position, self.voxel_data[index].voxel) else { continue; };
+            if let Err(error) = self.validate_voxel(voxel) {
+                result = Err(error);
+                break;
+            }
+            if self.store_voxel(index, position, voxel) != voxel {
+                changed += 1;
+                let position = Region::new(position, position);
+                changed_region = Some(
+                    changed_region
+                        .map_or(position, |changed_region| changed_region.union(&position)),
+                );
+            }
+        }
+
+        // keep faces consistent with whatever was written before an error
+        if let Some(changed_region) = changed_region {
+            self.refresh_faces(changed_region.expand(1));
+            self.notify_region(changed_region.expand(1));
+            self.publish_region_changed(changed_region);
+        }
+        self.commit_transaction();
+        result.map(|_| changed)
+    }
+}
+
+// shape extents are computed in i64 so large radii can't overflow, then clamped back to positions
+#[inline]
+fn clamp_i32(value: i64) -> i32 {
+    value.clamp(i32::MIN as i64, i32::MAX as i64) as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voxel::new_test_world;
+
+    // every position whose voxel differs between two snapshots of the same world
+    fn get_changes(before: &[(Offset3d<i32>, Voxel)], after: &World) -> Vec<Offset3d<i32>> {
+        let after = snapshot(after);
+        assert_eq!(before.len(), after.len());
+        before
+            .iter()
+            .zip(after)
+            .filter(|(before, after)| before.1 != after.1)
+            .map(|(_, (position, _))| position)
+            .collect()
+    }
+
+    fn snapshot(world: &World) -> Vec<(Offset3d<i32>, Voxel)> {
+        world
+            .voxels(world.bounds())
+            .map(|(position, &voxel)| (position, voxel))
+            .collect()
+    }
+
+    fn pos(x: i32, y: i32, z: i32) -> Offset3d<i32> {
+        Offset3d::new(x, y, z)
+    }
+
+    #[test]
+    fn fill_and_replace() {
+        let mut world = new_test_world();
+        let region = Region::new(pos(-1, 0, 0), pos(0, 1, 0));
+        let before = snapshot(&world);
+        assert_eq!(world.fill(region, Voxel::Tile(1)), Ok(4));
+        assert_eq!(
+            get_changes(&before, &world),
+            region.positions().collect::<Vec<_>>()
+        );
+        // already filled voxels don't count
+        assert_eq!(world.fill(region, Voxel::Tile(1)), Ok(0));
+        // clipped to the world
+        let outside = Region::new(pos(3, 3, 3), pos(10, 10, 10));
+        assert_eq!(world.fill(outside, Voxel::Tile(0)), Ok(1));
+        assert_eq!(world.fill(outside.expand(-3), Voxel::Tile(0)), Ok(0));
+        assert!(world.fill(region, Voxel::Tile(3)).is_err());
+
+        world.set_voxel(pos(0, 0, 0), Voxel::Tile(2)).unwrap();
+        let before = snapshot(&world);
+        assert_eq!(
+            world.replace(world.bounds(), Voxel::Tile(1), Voxel::Void),
+            Ok(3)
+        );
+        assert_eq!(
+            get_changes(&before, &world),
+            [pos(-1, 0, 0), pos(-1, 1, 0), pos(0, 1, 0)]
+        );
+        assert_eq!(world.get_voxel(pos(0, 0, 0)), Some(&Voxel::Tile(2)));
+        assert!(world.validate().is_empty());
+    }
+
+    #[test]
+    fn fill_sphere() {
+        let mut world = new_test_world();
+        assert_eq!(world.fill_sphere(pos(0, 0, 0), 0, Voxel::Tile(1)), Ok(1));
+        let before = snapshot(&world);
+        // a radius of 1 adds the 6 face and 12 edge neighbours, the corners lie beyond 1.5
+        assert_eq!(world.fill_sphere(pos(0, 0, 0), 1, Voxel::Tile(1)), Ok(18));
+        let changes = get_changes(&before, &world);
+        assert_eq!(changes.len(), 18);
+        for position in changes {
+            let distance =
+                position.x * position.x + position.y * position.y + position.z * position.z;
+            assert!((1..=2).contains(&distance));
+        }
+        assert_eq!(world.get_voxel(pos(1, 1, 1)), Some(&Voxel::Void));
+
+        // and a radius of 2 everything up to a squared distance of 6
+        assert_eq!(
+            world.fill_sphere(pos(0, 0, 0), 2, Voxel::Tile(1)),
+            Ok(81 - 19)
+        );
+        assert_eq!(world.get_voxel(pos(2, 1, 1)), Some(&Voxel::Tile(1)));
+        assert_eq!(world.get_voxel(pos(2, 2, 0)), Some(&Voxel::Void));
+        assert!(world.validate().is_empty());
+    }
+
+    #[test]
+    fn fill_cylinder() {
+        let mut world = new_test_world();
+        assert_eq!(
+            world.fill_cylinder(pos(0, 0, 0), 1, 0, Voxel::Tile(1)),
+            Ok(0)
+        );
+        let before = snapshot(&world);
+        assert_eq!(
+            world.fill_cylinder(pos(0, -1, 0), 1, 2, Voxel::Tile(1)),
+            Ok(18)
+        );
+        let expected: Vec<_> = Region::new(pos(-1, -1, -1), pos(1, 0, 1))
+            .positions()
+            .collect();
+        assert_eq!(get_changes(&before, &world), expected);
+        // a radius of 2 leaves out the four columns at the corners
+        assert_eq!(
+            world.fill_cylinder(pos(0, 0, 0), 2, 1, Voxel::Tile(2)),
+            Ok(21)
+        );
+        assert_eq!(world.get_voxel(pos(2, 0, 2)), Some(&Voxel::Void));
+        assert_eq!(world.get_voxel(pos(2, 0, 1)), Some(&Voxel::Tile(2)));
+        assert!(world.validate().is_empty());
+    }
+
+    #[test]
+    fn hollow_and_walls() {
+        let mut world = new_test_world();
+        let region = Region::new(pos(-2, -2, -2), pos(2, 2, 2));
+        world.fill(region, Voxel::Tile(1)).unwrap();
+        let before = snapshot(&world);
+        assert_eq!(world.hollow(region), Ok(27));
+        assert_eq!(
+            get_changes(&before, &world),
+            region.expand(-1).positions().collect::<Vec<_>>()
+        );
+        // nothing left is enclosed
+        assert_eq!(world.hollow(region), Ok(0));
+        assert!(world.validate().is_empty());
+
+        let mut world = new_test_world();
+        let region = Region::new(pos(-1, 0, -1), pos(1, 1, 1));
+        let before = snapshot(&world);
+        assert_eq!(world.walls(region, Voxel::Tile(2)), Ok(16));
+        let changes = get_changes(&before, &world);
+        assert_eq!(changes.len(), 16);
+        assert!(!changes.contains(&pos(0, 0, 0)) && !changes.contains(&pos(0, 1, 0)));
+        assert!(world.validate().is_empty());
+    }
+
+    #[test]
+    fn edits_near_i32_limits() {
+        let mut world = new_test_world();
+        let far = pos(i32::MAX, i32::MIN, i32::MAX);
+        assert_eq!(world.fill_sphere(far, u32::MAX, Voxel::Tile(1)), Ok(512));
+        assert_eq!(
+            world.fill_cylinder(far, u32::MAX, u32::MAX, Voxel::Tile(2)),
+            Ok(512)
+        );
+        assert_eq!(world.fill_sphere(far, 1, Voxel::Tile(0)), Ok(0));
+        // everything inside the 8x8x8 shell is enclosed
+        let everything = Region::new(far, pos(i32::MIN, i32::MAX, i32::MIN));
+        assert_eq!(world.hollow(everything), Ok(216));
+
+        // regions reaching the limits are clipped to the world, their walls lie outside it
+        assert_eq!(world.walls(everything, Voxel::Tile(0)), Ok(0));
+        assert_eq!(
+            world.replace(everything, Voxel::Tile(2), Voxel::Tile(0)),
+            Ok(512 - 216)
+        );
+        let top = pos(0, i32::MAX, 0);
+        assert_eq!(world.fill_cylinder(top, 1, u32::MAX, Voxel::Void), Ok(0));
+        // only the floor and ceiling of the shell are in the way
+        let bottom = pos(0, i32::MIN, 0);
+        assert_eq!(
+            world.fill_cylinder(bottom, 1, u32::MAX, Voxel::Void),
+            Ok(18)
+        );
+        assert!(world.validate().is_empty());
+    }
+}
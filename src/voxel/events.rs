@@ -0,0 +1,244 @@
+use super::{Generator, Region, Voxel, World};
+use crate::Offset3d;
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    mem,
+    rc::{Rc, Weak},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WorldEvent {
+    VoxelChanged {
+        position: Offset3d<i32>,
+        old_voxel: Voxel,
+        new_voxel: Voxel,
+    },
+    /// Any number of voxels inside the region changed at once.
+    RegionChanged(Region),
+    GenerationFinished {
+        generator: Generator,
+        seed: u64,
+    },
+}
+
+type EventQueue = RefCell<VecDeque<WorldEvent>>;
+
+/// A subscription to a world's events, each receiver gets its own copy of every event. Dropping
+/// it unsubscribes.
+#[derive(Debug)]
+pub struct EventReceiver {
+    events: Rc<EventQueue>,
+}
+
+impl EventReceiver {
+    // events in the order they happened
+    pub fn drain(&self) -> impl Iterator<Item = WorldEvent> {
+        mem::take(&mut *self.events.borrow_mut()).into_iter()
+    }
+
+    pub fn try_recv(&self) -> Option<WorldEvent> {
+        self.events.borrow_mut().pop_front()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.borrow().is_empty()
+    }
+}
+
+#[derive(Debug, Default)]
+pub(super) struct Events {
+    subscribers: Vec<Weak<EventQueue>>,
+    suppressed: u32,
+    // everything changed while suppressed, published as one region afterwards
+    suppressed_region: Option<Region>,
+}
+
+impl Events {
+    fn publish(&mut self, event: WorldEvent) {
+        self.subscribers
+            .retain(|subscriber| match subscriber.upgrade() {
+                Some(events) => {
+                    events.borrow_mut().push_back(event);
+                    true
+                }
+                None => false,
+            });
+    }
+
+    fn publish_change(&mut self, region: Region, event: WorldEvent) {
+        if self.subscribers.is_empty() {
+            return;
+        }
+        if self.suppressed > 0 {
+            self.suppressed_region = Some(match self.suppressed_region {
+                Some(suppressed_region) => suppressed_region.union(&region),
+                None => region,
+            });
+            return;
+        }
+        self.publish(event);
+    }
+}
+
+impl World {
+    pub fn subscribe(&mut self) -> EventReceiver {
+        let events = Rc::new(EventQueue::default());
+        self.events.subscribers.push(Rc::downgrade(&events));
+        EventReceiver { events }
+    }
+
+    /// Holds back change events until the matching [`World::resume_events`], which publishes a
+    /// single [`WorldEvent::RegionChanged`] covering everything that changed in between.
+    pub fn suppress_events(&mut self) {
+        self.events.suppressed += 1;
+    }
+
+    pub fn resume_events(&mut self) {
+        let events = &mut self.events;
+        if events.suppressed == 0 {
+            return;
+        }
+        events.suppressed -= 1;
+        if events.suppressed == 0 {
+            if let Some(region) = events.suppressed_region.take() {
+                events.publish(WorldEvent::RegionChanged(region));
+            }
+        }
+    }
+
+    pub(super) fn publish_voxel_changed(
+        &mut self,
+        position: Offset3d<i32>,
+        old_voxel: Voxel,
+        new_voxel: Voxel,
+    ) {
+        self.events.publish_change(
+            Region::new(position, position),
+            WorldEvent::VoxelChanged {
+                position,
+                old_voxel,
+                new_voxel,
+            },
+        );
+    }
+
+    pub(super) fn publish_region_changed(&mut self, region: Region) {
+        self.events
+            .publish_change(region, WorldEvent::RegionChanged(region));
+    }
+
+    pub(super) fn publish_generation_finished(&mut self, generator: Generator, seed: u64) {
+        if !self.events.subscribers.is_empty() {
+            self.events
+                .publish(WorldEvent::GenerationFinished { generator, seed });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voxel::new_test_world;
+
+    fn voxel_changed(position: Offset3d<i32>, old_voxel: Voxel, new_voxel: Voxel) -> WorldEvent {
+        WorldEvent::VoxelChanged {
+            position,
+            old_voxel,
+            new_voxel,
+        }
+    }
+
+    #[test]
+    fn subscribers_get_their_own_copy() {
+        let mut world = new_test_world();
+        let position = Offset3d::new(0, 0, 0);
+        world.set_voxel(position, Voxel::Tile(0)).unwrap();
+
+        let first = world.subscribe();
+        let second = world.subscribe();
+        world.set_voxel(position, Voxel::Tile(1)).unwrap();
+        let event = voxel_changed(position, Voxel::Tile(0), Voxel::Tile(1));
+        assert_eq!(first.try_recv(), Some(event));
+        assert!(first.is_empty());
+        assert_eq!(second.drain().collect::<Vec<_>>(), [event]);
+
+        // dropped receivers are unsubscribed
+        drop(second);
+        world.set_voxel(position, Voxel::Void).unwrap();
+        assert_eq!(world.events.subscribers.len(), 1);
+        assert_eq!(first.drain().count(), 1);
+    }
+
+    #[test]
+    fn events_arrive_in_order() {
+        let mut world = new_test_world();
+        let events = world.subscribe();
+        let (a, b) = (Offset3d::new(0, 0, 0), Offset3d::new(1, 2, 3));
+        world.set_voxel(a, Voxel::Tile(0)).unwrap();
+        world.set_voxel(b, Voxel::Tile(1)).unwrap();
+        let region = Region::new(a, b);
+        world.fill(region, Voxel::Tile(2)).unwrap();
+        world.set_voxel(a, Voxel::Void).unwrap();
+        assert_eq!(
+            events.drain().collect::<Vec<_>>(),
+            [
+                voxel_changed(a, Voxel::Void, Voxel::Tile(0)),
+                voxel_changed(b, Voxel::Void, Voxel::Tile(1)),
+                WorldEvent::RegionChanged(region),
+                voxel_changed(a, Voxel::Tile(2), Voxel::Void),
+            ]
+        );
+    }
+
+    #[test]
+    fn suppressed_changes_coalesce() {
+        let mut world = new_test_world();
+        let events = world.subscribe();
+        world.suppress_events();
+        world.suppress_events();
+        world
+            .set_voxel(Offset3d::new(-1, 0, 0), Voxel::Tile(0))
+            .unwrap();
+        world
+            .set_voxel(Offset3d::new(2, 1, 0), Voxel::Tile(1))
+            .unwrap();
+        world.resume_events();
+        assert!(events.is_empty());
+
+        world.resume_events();
+        let region = Region::new(Offset3d::new(-1, 0, 0), Offset3d::new(2, 1, 0));
+        assert_eq!(
+            events.drain().collect::<Vec<_>>(),
+            [WorldEvent::RegionChanged(region)]
+        );
+
+        // unbalanced resumes are ignored and nothing is published without changes
+        world.resume_events();
+        world.suppress_events();
+        world.resume_events();
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    fn undo_and_redo_publish_changes() {
+        let mut world = new_test_world();
+        let region = Region::new(Offset3d::new(0, 0, 0), Offset3d::new(1, 1, 0));
+        world.fill(region, Voxel::Tile(1)).unwrap();
+        let events = world.subscribe();
+
+        world.undo().unwrap();
+        assert_eq!(
+            events.drain().collect::<Vec<_>>(),
+            [WorldEvent::RegionChanged(region)]
+        );
+        world.redo().unwrap();
+        assert_eq!(
+            events.drain().collect::<Vec<_>>(),
+            [WorldEvent::RegionChanged(region)]
+        );
+        // nothing left to redo
+        world.redo().unwrap();
+        assert!(events.is_empty());
+    }
+}
@@ -0,0 +1,419 @@
+use super::{MeshData, Region, World};
+use crate::{impl_from_error, Color};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    io::{self, Write},
+    path::Path,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ExportFormat {
+    Obj,
+    Gltf,
+    Ply,
+}
+
+#[derive(Debug)]
+pub enum ExportError {
+    Io(io::Error),
+    Image(image::ImageError),
+    PathInvalid,
+}
+
+impl_from_error!(io::Error, ExportError, Io);
+impl_from_error!(image::ImageError, ExportError, Image);
+
+impl ExportFormat {
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_ascii_lowercase().as_str() {
+            "obj" => Some(Self::Obj),
+            "glb" => Some(Self::Gltf),
+            "ply" => Some(Self::Ply),
+            _ => None,
+        }
+    }
+}
+
+impl World {
+    const EXPORT_GENERATOR: &'static str = "ndrcraft";
+
+    /// Meshes `region` and writes it to `path`. OBJ exports also write a material library and a
+    /// texture per used tile section next to it, sharing the file name of `path`.
+    pub fn export(
+        &self,
+        path: &Path,
+        format: ExportFormat,
+        region: Region,
+        greedy: bool,
+    ) -> Result<(), ExportError> {
+        // checked before anything is created, so a bad path leaves no empty files behind
+        if format == ExportFormat::Obj && path.file_stem().is_none() {
+            return Err(ExportError::PathInvalid);
+        }
+        let mesh_data = self.build_mesh(region, greedy);
+        let mut writer = io::BufWriter::new(fs::File::create(path)?);
+        match format {
+            ExportFormat::Obj => {
+                let stem = path.file_stem().unwrap().to_string_lossy().into_owned();
+                let material_path = path.with_extension("mtl");
+                let material_library = material_path.file_name().unwrap().to_string_lossy();
+                self.write_obj(&mut writer, &mesh_data, &material_library)?;
+                let mut material_writer = fs::File::create(&material_path)?;
+                write_mtl(&mut material_writer, &mesh_data, &stem)?;
+                for layer in get_layers(&mesh_data) {
+                    let texture_path = path.with_file_name(get_texture_file_name(&stem, layer));
+                    self.build_section_image(layer).save(texture_path)?;
+                }
+            }
+            ExportFormat::Gltf => self.write_glb(&mut writer, &mesh_data)?,
+            ExportFormat::Ply => self.write_ply(&mut writer, &mesh_data)?,
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// One section of a tile's texture, matching a layer of the renderer's texture array. Mesh uvs
+    /// repeat over it once per voxel.
+    pub fn build_section_image(&self, layer: u32) -> image::RgbaImage {
+        let tile_index = layer / Self::TEXTURE_SECTIONS;
+        let section = layer % Self::TEXTURE_SECTIONS;
+        let (width, height) = (Self::SECTION_SIZE.width, Self::SECTION_SIZE.height);
+        let pixels = self
+            .tile_pixels
+            .get(tile_index as usize)
+            .and_then(Option::as_ref);
+        let fallback = to_rgba8(self.get_tile_display_color(tile_index));
+        image::RgbaImage::from_fn(width, height, |x, y| match pixels {
+            Some(pixels) => {
+                let offset = 4 * ((section * height + y) * width + x) as usize;
+                image::Rgba(pixels[offset..offset + 4].try_into().unwrap())
+            }
+            None => image::Rgba(fallback),
+        })
+    }
+
+    // positions and uvs with one group per submesh and layer, uvs are flipped since obj puts
+    // v = 0 at the bottom of the image
+    pub fn write_obj<W: Write>(
+        &self,
+        writer: &mut W,
+        mesh_data: &MeshData,
+        material_library: &str,
+    ) -> io::Result<()> {
+        writeln!(writer, "# {}", Self::EXPORT_GENERATOR)?;
+        writeln!(writer, "mtllib {material_library}")?;
+        for vertex in mesh_data.vertices.iter() {
+            let position = vertex.position;
+            writeln!(writer, "v {} {} {}", position.x, position.y, position.z)?;
+        }
+        for vertex in mesh_data.vertices.iter() {
+            writeln!(writer, "vt {} {}", vertex.uv.x, 1.0 - vertex.uv.y)?;
+        }
+        for ((submesh_index, layer), triangles) in get_material_groups(mesh_data) {
+            let name = get_material_name(submesh_index, layer);
+            writeln!(writer, "g {name}")?;
+            writeln!(writer, "usemtl {name}")?;
+            for [a, b, c] in triangles {
+                let (a, b, c) = (a + 1, b + 1, c + 1);
+                writeln!(writer, "f {a}/{a} {b}/{b} {c}/{c}")?;
+            }
+        }
+        Ok(())
+    }
+
+    // a single binary gltf 2.0 file with the used tile sections embedded as pngs
+    pub fn write_glb<W: Write>(
+        &self,
+        writer: &mut W,
+        mesh_data: &MeshData,
+    ) -> Result<(), ExportError> {
+        const ARRAY_BUFFER: u32 = 34962;
+        const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+        const FLOAT: u32 = 5126;
+        const UNSIGNED_INT: u32 = 5125;
+
+        let asset = format!(
+            r#""asset":{{"version":"2.0","generator":"{}"}}"#,
+            Self::EXPORT_GENERATOR
+        );
+        if mesh_data.is_empty() {
+            let json = format!(r#"{{{asset},"scene":0,"scenes":[{{"nodes":[]}}]}}"#);
+            return write_glb_chunks(writer, &json, &[]);
+        }
+
+        let mut buffer = Vec::new();
+        let mut buffer_views = Vec::new();
+        let mut push_view = |buffer: &mut Vec<u8>, bytes: &[u8], target: Option<u32>| {
+            let offset = buffer.len();
+            buffer.extend_from_slice(bytes);
+            buffer.resize(align_4(buffer.len()), 0);
+            let target = target.map_or(String::new(), |target| format!(r#","target":{target}"#));
+            buffer_views.push(format!(
+                r#"{{"buffer":0,"byteOffset":{offset},"byteLength":{}{target}}}"#,
+                bytes.len()
+            ));
+            buffer_views.len() - 1
+        };
+
+        let vertices = &mesh_data.vertices;
+        let positions: Vec<u8> = vertices
+            .iter()
+            .flat_map(|vertex| [vertex.position.x, vertex.position.y, vertex.position.z])
+            .flat_map(f32::to_le_bytes)
+            .collect();
+        let uvs: Vec<u8> = vertices
+            .iter()
+            .flat_map(|vertex| [vertex.uv.x, vertex.uv.y])
+            .flat_map(f32::to_le_bytes)
+            .collect();
+        let colors: Vec<u8> = vertices
+            .iter()
+            .flat_map(|vertex| {
+                let color = vertex.color;
+                [color.r, color.g, color.b, color.a]
+            })
+            .flat_map(f32::to_le_bytes)
+            .collect();
+        let (mut min, mut max) = ([f32::MAX; 3], [f32::MIN; 3]);
+        for vertex in vertices.iter() {
+            let position = [vertex.position.x, vertex.position.y, vertex.position.z];
+            for (axis, value) in position.into_iter().enumerate() {
+                min[axis] = min[axis].min(value);
+                max[axis] = max[axis].max(value);
+            }
+        }
+
+        let count = vertices.len();
+        let mut accessors = vec![
+            format!(
+                r#"{{"bufferView":{},"componentType":{FLOAT},"count":{count},"type":"VEC3","min":[{},{},{}],"max":[{},{},{}]}}"#,
+                push_view(&mut buffer, &positions, Some(ARRAY_BUFFER)),
+                min[0],
+                min[1],
+                min[2],
+                max[0],
+                max[1],
+                max[2]
+            ),
+            format!(
+                r#"{{"bufferView":{},"componentType":{FLOAT},"count":{count},"type":"VEC2"}}"#,
+                push_view(&mut buffer, &uvs, Some(ARRAY_BUFFER))
+            ),
+            format!(
+                r#"{{"bufferView":{},"componentType":{FLOAT},"count":{count},"type":"VEC4"}}"#,
+                push_view(&mut buffer, &colors, Some(ARRAY_BUFFER))
+            ),
+        ];
+        let layers: Vec<u32> = get_layers(mesh_data).into_iter().collect();
+        let mut primitives = Vec::new();
+        let mut materials = Vec::new();
+        for ((submesh_index, layer), triangles) in get_material_groups(mesh_data) {
+            let indices: Vec<u8> = triangles
+                .iter()
+                .flatten()
+                .flat_map(|index| index.to_le_bytes())
+                .collect();
+            accessors.push(format!(
+                r#"{{"bufferView":{},"componentType":{UNSIGNED_INT},"count":{},"type":"SCALAR"}}"#,
+                push_view(&mut buffer, &indices, Some(ELEMENT_ARRAY_BUFFER)),
+                3 * triangles.len()
+            ));
+            primitives.push(format!(
+                r#"{{"attributes":{{"POSITION":0,"TEXCOORD_0":1,"COLOR_0":2}},"indices":{},"material":{}}}"#,
+                accessors.len() - 1,
+                materials.len()
+            ));
+            let texture = layers.binary_search(&layer).unwrap();
+            let alpha_mode = match submesh_index {
+                MeshData::FLUID_SUBMESH => r#","alphaMode":"BLEND""#,
+                _ => "",
+            };
+            materials.push(format!(
+                r#"{{"name":"{}","pbrMetallicRoughness":{{"baseColorTexture":{{"index":{texture}}},"metallicFactor":0.0}}{alpha_mode}}}"#,
+                get_material_name(submesh_index, layer)
+            ));
+        }
+
+        let mut textures = Vec::new();
+        let mut images = Vec::new();
+        for (texture, &layer) in layers.iter().enumerate() {
+            let mut image = Vec::new();
+            self.build_section_image(layer).write_to(
+                &mut io::Cursor::new(&mut image),
+                image::ImageOutputFormat::Png,
+            )?;
+            textures.push(format!(r#"{{"sampler":0,"source":{texture}}}"#));
+            images.push(format!(
+                r#"{{"bufferView":{},"mimeType":"image/png"}}"#,
+                push_view(&mut buffer, &image, None)
+            ));
+        }
+
+        // nearest filtering and repeated edges, like the renderer's sampler
+        let json = format!(
+            concat!(
+                r#"{{{},"scene":0,"scenes":[{{"nodes":[0]}}],"nodes":[{{"mesh":0}}],"#,
+                r#""meshes":[{{"primitives":[{}]}}],"materials":[{}],"textures":[{}],"#,
+                r#""samplers":[{{"magFilter":9728,"minFilter":9728,"wrapS":10497,"wrapT":10497}}],"#,
+                r#""images":[{}],"#,
+                r#""accessors":[{}],"bufferViews":[{}],"buffers":[{{"byteLength":{}}}]}}"#
+            ),
+            asset,
+            primitives.join(","),
+            materials.join(","),
+            textures.join(","),
+            images.join(","),
+            accessors.join(","),
+            buffer_views.join(","),
+            buffer.len()
+        );
+        write_glb_chunks(writer, &json, &buffer)
+    }
+
+    // binary ply with the tile colours baked into the vertices, for tools without texture support
+    pub fn write_ply<W: Write>(&self, writer: &mut W, mesh_data: &MeshData) -> io::Result<()> {
+        let triangle_count: usize = mesh_data
+            .submeshes
+            .iter()
+            .map(|submesh| submesh.indices.len() / 3)
+            .sum();
+        write!(
+            writer,
+            concat!(
+                "ply\nformat binary_little_endian 1.0\ncomment {}\n",
+                "element vertex {}\n",
+                "property float x\nproperty float y\nproperty float z\n",
+                "property float s\nproperty float t\n",
+                "property uchar red\nproperty uchar green\nproperty uchar blue\n",
+                "property uchar alpha\n",
+                "element face {}\nproperty list uchar uint vertex_indices\nend_header\n"
+            ),
+            Self::EXPORT_GENERATOR,
+            mesh_data.vertices.len(),
+            triangle_count
+        )?;
+
+        for vertex in mesh_data.vertices.iter() {
+            let tile_color = self.get_tile_display_color(vertex.layer / Self::TEXTURE_SECTIONS);
+            let position = vertex.position;
+            for value in [position.x, position.y, position.z, vertex.uv.x, vertex.uv.y] {
+                writer.write_all(&value.to_le_bytes())?;
+            }
+            let color = Color::new(
+                tile_color.r * vertex.color.r,
+                tile_color.g * vertex.color.g,
+                tile_color.b * vertex.color.b,
+                tile_color.a * vertex.color.a,
+            );
+            writer.write_all(&to_rgba8(color))?;
+        }
+        for submesh in mesh_data.submeshes.iter() {
+            for triangle in get_triangles(&submesh.indices) {
+                writer.write_all(&[3])?;
+                for index in triangle {
+                    writer.write_all(&index.to_le_bytes())?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+const MATERIAL_NAMES: [&str; 2] = ["tiles", "fluids"];
+
+// one material per group of `get_material_groups`, textures repeat by default in obj
+fn write_mtl<W: Write>(writer: &mut W, mesh_data: &MeshData, texture_stem: &str) -> io::Result<()> {
+    for (submesh_index, layer) in get_material_groups(mesh_data).into_keys() {
+        let opacity = match submesh_index {
+            MeshData::FLUID_SUBMESH => World::FLUID_COLOR.a,
+            _ => 1.0,
+        };
+        writeln!(writer, "newmtl {}", get_material_name(submesh_index, layer))?;
+        writeln!(writer, "Kd 1 1 1")?;
+        writeln!(writer, "d {opacity}")?;
+        writeln!(writer, "illum 1")?;
+        writeln!(
+            writer,
+            "map_Kd {}",
+            get_texture_file_name(texture_stem, layer)
+        )?;
+    }
+    Ok(())
+}
+
+// triangles grouped by submesh and texture layer, since every layer is exported as its own
+// texture so it can repeat across merged faces
+fn get_material_groups(mesh_data: &MeshData) -> BTreeMap<(usize, u32), Vec<[u32; 3]>> {
+    let mut groups: BTreeMap<_, Vec<_>> = BTreeMap::new();
+    for (submesh_index, submesh) in mesh_data.submeshes.iter().enumerate() {
+        for triangle in get_triangles(&submesh.indices) {
+            let layer = mesh_data.vertices[triangle[0] as usize].layer;
+            groups
+                .entry((submesh_index, layer))
+                .or_default()
+                .push(triangle);
+        }
+    }
+    groups
+}
+
+fn get_layers(mesh_data: &MeshData) -> BTreeSet<u32> {
+    mesh_data
+        .vertices
+        .iter()
+        .map(|vertex| vertex.layer)
+        .collect()
+}
+
+fn get_material_name(submesh_index: usize, layer: u32) -> String {
+    format!("{}_{layer}", MATERIAL_NAMES[submesh_index])
+}
+
+fn get_texture_file_name(stem: &str, layer: u32) -> String {
+    format!("{stem}_{layer}.png")
+}
+
+fn write_glb_chunks<W: Write>(
+    writer: &mut W,
+    json: &str,
+    buffer: &[u8],
+) -> Result<(), ExportError> {
+    let mut json = json.as_bytes().to_owned();
+    json.resize(align_4(json.len()), b' ');
+    let mut length = 12 + 8 + json.len();
+    if !buffer.is_empty() {
+        length += 8 + buffer.len();
+    }
+    writer.write_all(b"glTF")?;
+    writer.write_all(&2u32.to_le_bytes())?;
+    writer.write_all(&(length as u32).to_le_bytes())?;
+    writer.write_all(&(json.len() as u32).to_le_bytes())?;
+    writer.write_all(b"JSON")?;
+    writer.write_all(&json)?;
+    if !buffer.is_empty() {
+        writer.write_all(&(buffer.len() as u32).to_le_bytes())?;
+        writer.write_all(b"BIN\0")?;
+        writer.write_all(buffer)?;
+    }
+    Ok(())
+}
+
+// the renderer treats clockwise triangles as front facing, exported files expect the opposite
+fn get_triangles(indices: &[u32]) -> impl Iterator<Item = [u32; 3]> + '_ {
+    indices
+        .chunks_exact(3)
+        .map(|triangle| [triangle[0], triangle[2], triangle[1]])
+}
+
+// glb chunks and buffer views are 4 byte aligned
+#[inline]
+fn align_4(length: usize) -> usize {
+    (length + 3) & !3
+}
+
+#[inline]
+fn to_rgba8(color: Color<f32>) -> [u8; 4] {
+    [color.r, color.g, color.b, color.a]
+        .map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8)
+}
@@ -0,0 +1,387 @@
+use super::{Boundary, Fluid, Region, Voxel, World, WorldError};
+use crate::{impl_from_error, Extent3d, Offset3d};
+use std::io::{self, Read, Write};
+
+#[derive(Debug)]
+pub enum FileError {
+    Io(io::Error),
+    MagicInvalid,
+    VersionUnsupported(u32),
+    DataInvalid,
+    MappingInvalid(usize),
+    World(WorldError),
+}
+
+impl_from_error!(io::Error, FileError, Io);
+impl_from_error!(WorldError, FileError, World);
+
+// headers are checked against these before anything is allocated, since run-length encoded
+// voxels can claim any volume in a few bytes
+const MAX_TILES: u32 = 1 << 16;
+
+impl World {
+    /// The most voxels a world file may hold, larger worlds can't be loaded.
+    pub const MAX_VOLUME: u64 = 1 << 26;
+
+    const FILE_MAGIC: [u8; 4] = *b"NDRW";
+    const FILE_VERSION: u32 = 2;
+
+    // layout: magic, version, size, origin, max tiles, boundary, run-length encoded voxels
+    // version 1 files have no boundary
+    pub fn save<W: Write>(&self, writer: &mut W) -> Result<(), FileError> {
+        writer.write_all(&Self::FILE_MAGIC)?;
+        write_u32(writer, Self::FILE_VERSION)?;
+        write_u32(writer, self.size.width)?;
+        write_u32(writer, self.size.height)?;
+        write_u32(writer, self.size.depth)?;
+        write_i32(writer, self.origin_offset.x)?;
+        write_i32(writer, self.origin_offset.y)?;
+        write_i32(writer, self.origin_offset.z)?;
+        write_u32(writer, self.max_tiles)?;
+        write_u8(
+            writer,
+            match self.boundary {
+                Boundary::Void => 0,
+                Boundary::Solid => 1,
+                Boundary::Wrap => 2,
+            },
+        )?;
+        write_voxels(
+            writer,
+            self.voxel_data.iter().map(|voxel_data| voxel_data.voxel),
+        )?;
+        Ok(())
+    }
+
+    // loaded worlds are headless, faces are rebuilt from the voxels
+    pub fn load<R: Read>(reader: &mut R) -> Result<Self, FileError> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if magic != Self::FILE_MAGIC {
+            return Err(FileError::MagicInvalid);
+        }
+        let version = read_u32(reader)?;
+        if version == 0 || version > Self::FILE_VERSION {
+            return Err(FileError::VersionUnsupported(version));
+        }
+        let size = Extent3d::new(read_u32(reader)?, read_u32(reader)?, read_u32(reader)?);
+        let origin = Offset3d::new(read_i32(reader)?, read_i32(reader)?, read_i32(reader)?);
+        let max_tiles = read_u32(reader)?;
+        if max_tiles > MAX_TILES {
+            return Err(FileError::DataInvalid);
+        }
+        let boundary = match version {
+            1 => Boundary::Void,
+            _ => match read_u8(reader)? {
+                0 => Boundary::Void,
+                1 => Boundary::Solid,
+                2 => Boundary::Wrap,
+                _ => return Err(FileError::DataInvalid),
+            },
+        };
+        let Some(bounds) = Region::checked_from_size(origin, size) else { return Err(FileError::DataInvalid); };
+        checked_volume(size)?;
+
+        let mut world = Self::new_headless(bounds, max_tiles);
+        world.boundary = boundary;
+        let voxels = read_voxels(reader, world.voxel_data.len())?;
+        for (voxel_data, voxel) in world.voxel_data.iter_mut().zip(voxels) {
+            match voxel {
+                Voxel::Tile(tile_index) | Voxel::Fluid(Fluid { tile_index, .. })
+                    if tile_index >= max_tiles =>
+                {
+                    return Err(WorldError::TileIndexInvalid(tile_index).into());
+                }
+                _ => voxel_data.voxel = voxel,
+            }
+        }
+        world.refresh_faces(world.bounds());
+        Ok(world)
+    }
+}
+
+pub(super) fn write_voxels<W: Write>(
+    writer: &mut W,
+    voxels: impl Iterator<Item = Voxel>,
+) -> io::Result<()> {
+    let mut run: Option<(u32, Voxel)> = None;
+    for voxel in voxels {
+        run = match run {
+            Some((length, run_voxel)) if run_voxel == voxel && length < u32::MAX => {
+                Some((length + 1, run_voxel))
+            }
+            Some((length, run_voxel)) => {
+                write_run(writer, length, run_voxel)?;
+                Some((1, voxel))
+            }
+            None => Some((1, voxel)),
+        };
+    }
+    if let Some((length, run_voxel)) = run {
+        write_run(writer, length, run_voxel)?;
+    }
+    Ok(())
+}
+
+// the number of voxels in `size`, if it is valid and within `World::MAX_VOLUME`
+pub(super) fn checked_volume(size: Extent3d<u32>) -> Result<usize, FileError> {
+    (size.width as u64)
+        .checked_mul(size.height as u64)
+        .and_then(|area| area.checked_mul(size.depth as u64))
+        .filter(|volume| (1..=World::MAX_VOLUME).contains(volume))
+        .map(|volume| volume as usize)
+        .ok_or(FileError::DataInvalid)
+}
+
+pub(super) fn read_voxels<R: Read>(reader: &mut R, count: usize) -> Result<Vec<Voxel>, FileError> {
+    if count as u64 > World::MAX_VOLUME {
+        return Err(FileError::DataInvalid);
+    }
+    let mut voxels = Vec::with_capacity(count);
+    while voxels.len() < count {
+        let length = read_u32(reader)? as usize;
+        let voxel = match read_u8(reader)? {
+            0 => Voxel::Void,
+            1 => Voxel::Tile(read_u32(reader)?),
+            2 => {
+                let tile_index = read_u32(reader)?;
+                let level = read_u8(reader)?;
+                let source = read_u8(reader)? != 0;
+                if level == 0 || level > Fluid::MAX_LEVEL {
+                    return Err(FileError::DataInvalid);
+                }
+                Voxel::Fluid(Fluid {
+                    tile_index,
+                    level,
+                    source,
+                })
+            }
+            _ => return Err(FileError::DataInvalid),
+        };
+        if length == 0 || voxels.len() + length > count {
+            return Err(FileError::DataInvalid);
+        }
+        voxels.resize(voxels.len() + length, voxel);
+    }
+    Ok(voxels)
+}
+
+fn write_run<W: Write>(writer: &mut W, length: u32, voxel: Voxel) -> io::Result<()> {
+    write_u32(writer, length)?;
+    match voxel {
+        Voxel::Void => write_u8(writer, 0),
+        Voxel::Tile(tile_index) => {
+            write_u8(writer, 1)?;
+            write_u32(writer, tile_index)
+        }
+        Voxel::Fluid(fluid) => {
+            write_u8(writer, 2)?;
+            write_u32(writer, fluid.tile_index)?;
+            write_u8(writer, fluid.level)?;
+            write_u8(writer, fluid.source as u8)
+        }
+    }
+}
+
+#[inline]
+pub(super) fn write_u8<W: Write>(writer: &mut W, value: u8) -> io::Result<()> {
+    writer.write_all(&[value])
+}
+
+#[inline]
+pub(super) fn write_u32<W: Write>(writer: &mut W, value: u32) -> io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+#[inline]
+pub(super) fn write_i32<W: Write>(writer: &mut W, value: i32) -> io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+#[inline]
+pub(super) fn read_u8<R: Read>(reader: &mut R) -> io::Result<u8> {
+    let mut bytes = [0; 1];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes[0])
+}
+
+#[inline]
+pub(super) fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+#[inline]
+pub(super) fn read_i32<R: Read>(reader: &mut R) -> io::Result<i32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(i32::from_le_bytes(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voxel::new_test_world;
+
+    fn new_saved_world() -> World {
+        let mut world = new_test_world();
+        world.set_boundary(Boundary::Wrap);
+        world
+            .set_voxel(Offset3d::new(0, 0, 0), Voxel::Tile(2))
+            .unwrap();
+        world
+            .set_voxel(Offset3d::new(1, 0, 0), Voxel::Fluid(Fluid::new_source(1)))
+            .unwrap();
+        world
+    }
+
+    fn save(world: &World) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        world.save(&mut bytes).unwrap();
+        bytes
+    }
+
+    // a version 2 header for `size` at the origin, followed by `voxels`
+    fn encode(size: Extent3d<u32>, max_tiles: u32, voxels: &[(u32, Voxel)]) -> Vec<u8> {
+        let mut bytes = World::FILE_MAGIC.to_vec();
+        for value in [
+            World::FILE_VERSION,
+            size.width,
+            size.height,
+            size.depth,
+            0,
+            0,
+            0,
+        ] {
+            write_u32(&mut bytes, value).unwrap();
+        }
+        write_u32(&mut bytes, max_tiles).unwrap();
+        write_u8(&mut bytes, 0).unwrap();
+        for &(length, voxel) in voxels {
+            write_run(&mut bytes, length, voxel).unwrap();
+        }
+        bytes
+    }
+
+    #[test]
+    fn round_trip() {
+        let world = new_saved_world();
+        let loaded = World::load(&mut save(&world).as_slice()).unwrap();
+        assert_eq!(loaded.bounds(), world.bounds());
+        assert_eq!(loaded.max_tiles(), world.max_tiles());
+        assert_eq!(loaded.boundary(), Boundary::Wrap);
+        for position in world.bounds().positions() {
+            assert_eq!(loaded.get_voxel(position), world.get_voxel(position));
+            assert_eq!(loaded.get_faces(position), world.get_faces(position));
+        }
+    }
+
+    #[test]
+    fn round_trip_off_centre() {
+        let mut world = new_saved_world();
+        world.resize(Region::new(
+            Offset3d::new(-1, -20, 5),
+            Offset3d::new(6, -17, 9),
+        ));
+        world.expand(Region::new(
+            Offset3d::new(-3, 0, 0),
+            Offset3d::new(-3, 0, 0),
+        ));
+        world
+            .set_voxel(Offset3d::new(6, -17, 9), Voxel::Tile(1))
+            .unwrap();
+        world
+            .set_voxel(Offset3d::new(-3, -20, 0), Voxel::Tile(2))
+            .unwrap();
+
+        let loaded = World::load(&mut save(&world).as_slice()).unwrap();
+        assert_eq!(loaded.bounds(), world.bounds());
+        assert_eq!(loaded.bounds().min, Offset3d::new(-3, -20, 0));
+        for position in world.bounds().positions() {
+            assert_eq!(loaded.get_voxel(position), world.get_voxel(position));
+            assert_eq!(loaded.get_faces(position), world.get_faces(position));
+        }
+        assert!(loaded.validate().is_empty());
+    }
+
+    #[test]
+    fn truncated_input() {
+        let bytes = save(&new_saved_world());
+        for length in [0, 3, 10, bytes.len() - 1] {
+            let result = World::load(&mut &bytes[..length]);
+            assert!(matches!(result, Err(FileError::Io(_))), "length {length}");
+        }
+    }
+
+    #[test]
+    fn header_errors() {
+        let mut bytes = save(&new_saved_world());
+        bytes[0] = b'X';
+        assert!(matches!(
+            World::load(&mut bytes.as_slice()),
+            Err(FileError::MagicInvalid)
+        ));
+
+        let mut bytes = save(&new_saved_world());
+        bytes[4..8].copy_from_slice(&3u32.to_le_bytes());
+        assert!(matches!(
+            World::load(&mut bytes.as_slice()),
+            Err(FileError::VersionUnsupported(3))
+        ));
+
+        // huge volumes and tile counts are rejected before allocating
+        let huge = Extent3d::new(u32::MAX, u32::MAX, u32::MAX);
+        let bytes = encode(huge, 3, &[(1, Voxel::Void)]);
+        assert!(matches!(
+            World::load(&mut bytes.as_slice()),
+            Err(FileError::DataInvalid)
+        ));
+        let bytes = encode(Extent3d::new(1, 1, 1), u32::MAX, &[(1, Voxel::Void)]);
+        assert!(matches!(
+            World::load(&mut bytes.as_slice()),
+            Err(FileError::DataInvalid)
+        ));
+        let bytes = encode(Extent3d::new(0, 1, 1), 3, &[]);
+        assert!(matches!(
+            World::load(&mut bytes.as_slice()),
+            Err(FileError::DataInvalid)
+        ));
+    }
+
+    #[test]
+    fn voxel_errors() {
+        let size = Extent3d::new(2, 1, 1);
+        let load = |voxels: &[(u32, Voxel)]| World::load(&mut encode(size, 3, voxels).as_slice());
+
+        assert!(load(&[(2, Voxel::Tile(1))]).is_ok());
+        assert!(matches!(
+            load(&[(3, Voxel::Void)]),
+            Err(FileError::DataInvalid)
+        ));
+        assert!(matches!(
+            load(&[(0, Voxel::Void), (2, Voxel::Void)]),
+            Err(FileError::DataInvalid)
+        ));
+        assert!(matches!(
+            load(&[(2, Voxel::Tile(3))]),
+            Err(FileError::World(WorldError::TileIndexInvalid(3)))
+        ));
+        let fluid = Fluid {
+            tile_index: 0,
+            level: Fluid::MAX_LEVEL + 1,
+            source: false,
+        };
+        assert!(matches!(
+            load(&[(2, Voxel::Fluid(fluid))]),
+            Err(FileError::DataInvalid)
+        ));
+
+        let mut bytes = encode(size, 3, &[(2, Voxel::Void)]);
+        *bytes.last_mut().unwrap() = 7;
+        assert!(matches!(
+            World::load(&mut bytes.as_slice()),
+            Err(FileError::DataInvalid)
+        ));
+    }
+}
@@ -0,0 +1,288 @@
+use super::{Face, Voxel, World, WorldError};
+use crate::Offset3d;
+
+pub(super) const FLOW_DELAY: u64 = 1;
+const SPREAD_FACES: [Face; 4] = [Face::PosX, Face::NegX, Face::PosZ, Face::NegZ];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Fluid {
+    pub tile_index: u32,
+    pub level: u8,
+    pub source: bool,
+}
+
+impl Fluid {
+    pub const MAX_LEVEL: u8 = 8;
+
+    pub const fn new_source(tile_index: u32) -> Self {
+        Self {
+            tile_index,
+            level: Self::MAX_LEVEL,
+            source: true,
+        }
+    }
+
+    pub const fn new_flowing(tile_index: u32, level: u8) -> Self {
+        Self {
+            tile_index,
+            level,
+            source: false,
+        }
+    }
+}
+
+impl World {
+    pub(super) fn update_fluid(&mut self, position: Offset3d<i32>) -> Result<(), WorldError> {
+        let Some(&Voxel::Fluid(fluid)) = self.get_voxel(position) else { return Ok(()); };
+
+        // flowing fluid settles to the level its neighbours feed it and drains once nothing does
+        if !fluid.source {
+            let level = self.get_fed_level(position, fluid.tile_index);
+            if level != fluid.level {
+                let voxel = match level {
+                    0 => Voxel::Void,
+                    level => Voxel::Fluid(Fluid::new_flowing(fluid.tile_index, level)),
+                };
+                self.set_voxel(position, voxel)?;
+                return Ok(());
+            }
+        }
+
+        // fall downhill first
+        let below = position + Face::NegY.get_voxel_normal();
+        if let Some(Voxel::Void) = self.get_voxel(below) {
+            let voxel = Voxel::Fluid(Fluid::new_flowing(fluid.tile_index, Fluid::MAX_LEVEL));
+            self.set_voxel(below, voxel)?;
+            return Ok(());
+        }
+
+        // then spread outwards, losing a level per voxel
+        if !self.is_fluid_spreading(position, fluid) {
+            return Ok(());
+        }
+        let level = fluid.level - 1;
+        for face in SPREAD_FACES {
+            let other_position = position + face.get_voxel_normal();
+            let spread = match self.get_voxel(other_position) {
+                Some(Voxel::Void) => true,
+                Some(Voxel::Fluid(other)) => {
+                    other.tile_index == fluid.tile_index && !other.source && other.level < level
+                }
+                _ => false,
+            };
+            if spread {
+                let voxel = Voxel::Fluid(Fluid::new_flowing(fluid.tile_index, level));
+                self.set_voxel(other_position, voxel)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub(super) fn get_fluid_height(&self, position: Offset3d<i32>, fluid: Fluid) -> f32 {
+        let above = position + Face::PosY.get_voxel_normal();
+        match self.get_voxel(above) {
+            Some(Voxel::Fluid(other)) if other.tile_index == fluid.tile_index => 1.0,
+            _ => fluid.level as f32 / Fluid::MAX_LEVEL as f32,
+        }
+    }
+
+    fn get_fed_level(&self, position: Offset3d<i32>, tile_index: u32) -> u8 {
+        let above = position + Face::PosY.get_voxel_normal();
+        if let Some(Voxel::Fluid(other)) = self.get_voxel(above) {
+            if other.tile_index == tile_index {
+                return Fluid::MAX_LEVEL;
+            }
+        }
+        SPREAD_FACES
+            .iter()
+            .filter_map(|face| {
+                let other_position = position + face.get_voxel_normal();
+                match self.get_voxel(other_position) {
+                    Some(&Voxel::Fluid(other))
+                        if other.tile_index == tile_index
+                            && self.is_fluid_spreading(other_position, other) =>
+                    {
+                        Some(other.level - 1)
+                    }
+                    _ => None,
+                }
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    // fluid only spreads sideways once it rests on something
+    fn is_fluid_spreading(&self, position: Offset3d<i32>, fluid: Fluid) -> bool {
+        if fluid.level <= 1 {
+            return false;
+        }
+        let below = position + Face::NegY.get_voxel_normal();
+        match self.get_voxel(below) {
+            Some(Voxel::Void) => false,
+            Some(Voxel::Fluid(other)) => other.tile_index != fluid.tile_index,
+            _ => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voxel::{new_test_world, Faces, Region};
+
+    const WATER: u32 = 1;
+
+    // a world with a solid floor at the bottom layer
+    fn new_floored_world() -> World {
+        let mut world = new_test_world();
+        let Region { min, max } = world.bounds();
+        let floor = Region::new(min, Offset3d::new(max.x, min.y, max.z));
+        world.fill(floor, Voxel::Tile(0)).unwrap();
+        world
+    }
+
+    fn run_ticks(world: &mut World, count: u32) {
+        for _ in 0..count {
+            world.tick().unwrap();
+        }
+    }
+
+    fn get_level(world: &World, position: Offset3d<i32>) -> Option<u8> {
+        match world.get_voxel(position) {
+            Some(Voxel::Fluid(fluid)) => Some(fluid.level),
+            _ => None,
+        }
+    }
+
+    fn count_fluids(world: &World) -> usize {
+        world
+            .voxels(world.bounds())
+            .filter(|(_, voxel)| matches!(voxel, Voxel::Fluid(_)))
+            .count()
+    }
+
+    #[test]
+    fn source_spreads_losing_a_level_per_step() {
+        let mut world = new_floored_world();
+        let source = Offset3d::new(0, -3, 0);
+        world
+            .set_voxel(source, Voxel::Fluid(Fluid::new_source(WATER)))
+            .unwrap();
+        run_ticks(&mut world, 32);
+
+        assert_eq!(get_level(&world, source), Some(Fluid::MAX_LEVEL));
+        for step in 1..=3 {
+            let expected = Some(Fluid::MAX_LEVEL - step as u8);
+            assert_eq!(
+                get_level(&world, source + Offset3d::new(step, 0, 0)),
+                expected
+            );
+            assert_eq!(
+                get_level(&world, source + Offset3d::new(-step, 0, 0)),
+                expected
+            );
+            assert_eq!(
+                get_level(&world, source + Offset3d::new(0, 0, step)),
+                expected
+            );
+        }
+        assert_eq!(
+            get_level(&world, source + Offset3d::new(1, 0, 1)),
+            Some(Fluid::MAX_LEVEL - 2)
+        );
+        // nothing climbs above the source
+        assert_eq!(get_level(&world, source + Offset3d::new(0, 1, 0)), None);
+    }
+
+    #[test]
+    fn falls_into_gaps_at_full_level() {
+        let mut world = new_floored_world();
+        // a platform two voxels above the floor with a one voxel gap next to the source
+        let platform = Region::new(Offset3d::new(-4, -2, -4), Offset3d::new(3, -2, 3));
+        world.fill(platform, Voxel::Tile(0)).unwrap();
+        world
+            .set_voxel(Offset3d::new(1, -2, 0), Voxel::Void)
+            .unwrap();
+        world
+            .set_voxel(
+                Offset3d::new(0, -1, 0),
+                Voxel::Fluid(Fluid::new_source(WATER)),
+            )
+            .unwrap();
+        run_ticks(&mut world, 32);
+
+        assert_eq!(
+            get_level(&world, Offset3d::new(1, -1, 0)),
+            Some(Fluid::MAX_LEVEL - 1)
+        );
+        assert_eq!(
+            get_level(&world, Offset3d::new(1, -2, 0)),
+            Some(Fluid::MAX_LEVEL)
+        );
+        assert_eq!(
+            get_level(&world, Offset3d::new(1, -3, 0)),
+            Some(Fluid::MAX_LEVEL)
+        );
+        assert_eq!(
+            get_level(&world, Offset3d::new(2, -3, 0)),
+            Some(Fluid::MAX_LEVEL - 1)
+        );
+        // falling fluid is drawn full height, while the fluid it lands in isn't
+        let Some(&Voxel::Fluid(fluid)) = world.get_voxel(Offset3d::new(1, -2, 0)) else {
+            panic!()
+        };
+        assert_eq!(world.get_fluid_height(Offset3d::new(1, -2, 0), fluid), 1.0);
+    }
+
+    #[test]
+    fn flowing_fluid_drains_without_a_source() {
+        let mut world = new_floored_world();
+        let source = Offset3d::new(0, -3, 0);
+        world
+            .set_voxel(source, Voxel::Fluid(Fluid::new_source(WATER)))
+            .unwrap();
+        run_ticks(&mut world, 32);
+        assert!(count_fluids(&world) > 1);
+
+        world.set_voxel(source, Voxel::Void).unwrap();
+        run_ticks(&mut world, 64);
+        assert_eq!(count_fluids(&world), 0);
+    }
+
+    #[test]
+    fn face_rules() {
+        let tile = Voxel::Tile(0);
+        let full = Voxel::Fluid(Fluid::new_source(WATER));
+        let low = Voxel::Fluid(Fluid::new_flowing(WATER, 3));
+
+        assert!(!tile.is_face_visible(Face::PosX, tile));
+        assert!(tile.is_face_visible(Face::PosX, Voxel::Void));
+        assert!(tile.is_face_visible(Face::PosX, full));
+        assert!(!full.is_face_visible(Face::PosX, tile));
+        assert!(full.is_face_visible(Face::PosY, Voxel::Void));
+        assert!(!Voxel::Void.is_face_visible(Face::PosX, tile));
+
+        // sides show above lower fluids only, fluid stacked on fluid never does
+        assert!(full.is_face_visible(Face::NegZ, low));
+        assert!(!low.is_face_visible(Face::PosZ, full));
+        assert!(!full.is_face_visible(Face::NegZ, full));
+        assert!(!full.is_face_visible(Face::PosY, low));
+        assert!(!low.is_face_visible(Face::NegY, full));
+    }
+
+    #[test]
+    fn world_faces_follow_levels() {
+        let mut world = new_test_world();
+        let (high, low) = (Offset3d::new(0, 0, 0), Offset3d::new(1, 0, 0));
+        world
+            .set_voxel(high, Voxel::Fluid(Fluid::new_source(WATER)))
+            .unwrap();
+        world
+            .set_voxel(low, Voxel::Fluid(Fluid::new_flowing(WATER, 2)))
+            .unwrap();
+        let faces = |position| world.get_faces(position).unwrap();
+        assert!(faces(high).contains(Faces::POS_X));
+        assert!(!faces(low).contains(Faces::NEG_X));
+        assert!(world.validate().is_empty());
+    }
+}
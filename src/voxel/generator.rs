@@ -0,0 +1,98 @@
+use super::{Voxel, World, WorldError};
+use crate::{Offset3d, Random};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Generator {
+    Flat,
+    #[default]
+    Waves,
+    Hills,
+}
+
+impl Generator {
+    pub const ALL: [Self; 3] = [Self::Flat, Self::Waves, Self::Hills];
+    /// Every generator places tiles below this index.
+    pub const TILE_COUNT: u32 = 3;
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Flat => "flat",
+            Self::Waves => "waves",
+            Self::Hills => "hills",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|generator| generator.name() == name)
+    }
+
+    fn get_voxel(&self, seed: u64, position: Offset3d<i32>) -> Voxel {
+        match self {
+            Self::Flat => match position.y {
+                0 => Voxel::Tile(1),
+                y if y < 0 => Voxel::Tile(2),
+                _ => Voxel::Void,
+            },
+            Self::Waves => {
+                let threshold =
+                    (position.x as f32 * 0.12).sin() * 1.2 + (position.z as f32 * 0.05).cos() * 0.5;
+                if position.y > 0 || position.y as f32 > threshold {
+                    return Voxel::Void;
+                }
+                Voxel::Tile((hash_position(seed, position) % Self::TILE_COUNT as u64) as u32)
+            }
+            Self::Hills => {
+                let (x, z) = (position.x as f32, position.z as f32);
+                let height = value_noise(seed, x / 24.0, z / 24.0) * 6.0
+                    + value_noise(seed.wrapping_add(1), x / 8.0, z / 8.0) * 2.0
+                    - 4.0;
+                let surface = height.floor() as i32;
+                match surface - position.y {
+                    depth if depth < 0 => Voxel::Void,
+                    0 => Voxel::Tile(1),
+                    1..=3 => Voxel::Tile(0),
+                    _ => Voxel::Tile(2),
+                }
+            }
+        }
+    }
+}
+
+impl World {
+    pub fn generate(&mut self, generator: Generator, seed: u64) -> Result<(), WorldError> {
+        self.set_seed(seed);
+        // generated terrain is the starting point, not an edit to undo
+        let recording = self.history.set_recording(false);
+        let result = self.edit_region(self.bounds(), "generate", |position, _| {
+            Some(generator.get_voxel(seed, position))
+        });
+        self.history.set_recording(recording);
+        result?;
+        self.publish_generation_finished(generator, seed);
+        Ok(())
+    }
+}
+
+#[inline]
+fn hash_position(seed: u64, position: Offset3d<i32>) -> u64 {
+    let x = (position.x as u32 as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    let y = (position.y as u32 as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F);
+    let z = (position.z as u32 as u64).wrapping_mul(0x1656_67B1_9E37_79F9);
+    Random::new(seed ^ x ^ y.rotate_left(21) ^ z.rotate_left(42)).next_u64()
+}
+
+// smoothly interpolated lattice noise in 0.0..1.0
+fn value_noise(seed: u64, x: f32, z: f32) -> f32 {
+    let lattice = |x: i32, z: i32| {
+        let hash = hash_position(seed, Offset3d::new(x, 0, z));
+        (hash >> 40) as f32 / (1u64 << 24) as f32
+    };
+    let (x0, z0) = (x.floor() as i32, z.floor() as i32);
+    let smooth = |t: f32| t * t * (3.0 - 2.0 * t);
+    let (tx, tz) = (smooth(x - x0 as f32), smooth(z - z0 as f32));
+    let top = lattice(x0, z0) + (lattice(x0 + 1, z0) - lattice(x0, z0)) * tx;
+    let bottom = lattice(x0, z0 + 1) + (lattice(x0 + 1, z0 + 1) - lattice(x0, z0 + 1)) * tx;
+    top + (bottom - top) * tz
+}
@@ -0,0 +1,351 @@
+use super::{Voxel, World, WorldError};
+use crate::Offset3d;
+use std::{collections::VecDeque, mem};
+
+#[derive(Clone, Copy, Debug)]
+struct Edit {
+    position: Offset3d<i32>,
+    old_voxel: Voxel,
+    new_voxel: Voxel,
+}
+
+#[derive(Debug)]
+struct Transaction {
+    name: String,
+    edits: Vec<Edit>,
+}
+
+// undo and redo stacks of transactions, capped by the total number of edits they hold rather
+// than their size in bytes
+#[derive(Debug)]
+pub(super) struct History {
+    undo: VecDeque<Transaction>,
+    redo: Vec<Transaction>,
+    current: Option<Transaction>,
+    depth: u32,
+    edit_count: usize,
+    max_edits: usize,
+    // the open transaction outgrew `max_edits` and is no longer recorded
+    overflowed: bool,
+    recording: bool,
+}
+
+impl History {
+    const DEFAULT_MAX_EDITS: usize = 1 << 20;
+    const IMPLICIT_NAME: &'static str = "edit";
+
+    pub(super) fn new() -> Self {
+        Self {
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+            current: None,
+            depth: 0,
+            edit_count: 0,
+            max_edits: Self::DEFAULT_MAX_EDITS,
+            overflowed: false,
+            recording: true,
+        }
+    }
+
+    // bytes held by every journaled edit, including the redo stack
+    pub(super) fn memory_used(&self) -> usize {
+        let edit_count: usize = (self.undo.iter().chain(&self.redo).chain(&self.current))
+            .map(|transaction| transaction.edits.capacity())
+            .sum();
+        edit_count * mem::size_of::<Edit>()
+    }
+
+    // returns the previous state so callers can restore it
+    pub(super) fn set_recording(&mut self, recording: bool) -> bool {
+        mem::replace(&mut self.recording, recording)
+    }
+
+    pub(super) fn record(&mut self, position: Offset3d<i32>, old_voxel: Voxel, new_voxel: Voxel) {
+        if !self.recording || self.max_edits == 0 {
+            return;
+        }
+        let edit = Edit {
+            position,
+            old_voxel,
+            new_voxel,
+        };
+        match self.current.as_mut() {
+            Some(_) if self.overflowed => {}
+            // a transaction that can't fit would be trimmed along with everything older once
+            // committed, so that happens right away instead of letting it grow unbounded
+            Some(transaction) if transaction.edits.len() >= self.max_edits => {
+                transaction.edits = Vec::new();
+                self.overflowed = true;
+                self.clear();
+            }
+            Some(transaction) => transaction.edits.push(edit),
+            None => self.push(Transaction {
+                name: Self::IMPLICIT_NAME.to_owned(),
+                edits: vec![edit],
+            }),
+        }
+    }
+
+    fn push(&mut self, transaction: Transaction) {
+        self.redo.clear();
+        self.edit_count += transaction.edits.len();
+        self.undo.push_back(transaction);
+        self.trim();
+    }
+
+    // drop the oldest transactions until the journal fits again
+    fn trim(&mut self) {
+        while self.edit_count > self.max_edits {
+            let Some(transaction) = self.undo.pop_front() else { break; };
+            self.edit_count -= transaction.edits.len();
+        }
+    }
+
+    fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.edit_count = 0;
+    }
+}
+
+impl World {
+    /// Groups every edit until the matching [`World::commit_transaction`] into one undo step.
+    /// Nested transactions fold into the outermost one.
+    pub fn begin_transaction(&mut self, name: &str) {
+        let history = &mut self.history;
+        if history.depth == 0 {
+            history.overflowed = false;
+            history.current = Some(Transaction {
+                name: name.to_owned(),
+                edits: Vec::new(),
+            });
+        }
+        history.depth += 1;
+    }
+
+    pub fn commit_transaction(&mut self) {
+        let history = &mut self.history;
+        if history.depth == 0 {
+            return;
+        }
+        history.depth -= 1;
+        if history.depth > 0 {
+            return;
+        }
+        if let Some(transaction) = history.current.take() {
+            if !transaction.edits.is_empty() {
+                history.push(transaction);
+            }
+        }
+    }
+
+    pub fn is_transaction_open(&self) -> bool {
+        self.history.depth > 0
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.history.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.history.redo.is_empty()
+    }
+
+    /// Reverts the most recent transaction and returns its name. Voxels that changed since, like
+    /// the ones the simulation moved, are left as they are.
+    pub fn undo(&mut self) -> Result<Option<String>, WorldError> {
+        if self.is_transaction_open() {
+            return Err(WorldError::TransactionOpen);
+        }
+        let Some(transaction) = self.history.undo.pop_back() else { return Ok(None); };
+        self.history.edit_count -= transaction.edits.len();
+        let edits = transaction.edits.iter().rev();
+        self.apply_edits(edits.map(|edit| (edit.position, edit.new_voxel, edit.old_voxel)))?;
+        let name = transaction.name.clone();
+        self.history.redo.push(transaction);
+        Ok(Some(name))
+    }
+
+    /// Reapplies the most recently undone transaction and returns its name, skipping voxels that
+    /// changed since like [`World::undo`].
+    pub fn redo(&mut self) -> Result<Option<String>, WorldError> {
+        if self.is_transaction_open() {
+            return Err(WorldError::TransactionOpen);
+        }
+        let Some(transaction) = self.history.redo.pop() else { return Ok(None); };
+        let edits = transaction.edits.iter();
+        self.apply_edits(edits.map(|edit| (edit.position, edit.old_voxel, edit.new_voxel)))?;
+        let name = transaction.name.clone();
+        self.history.edit_count += transaction.edits.len();
+        self.history.undo.push_back(transaction);
+        self.history.trim();
+        Ok(Some(name))
+    }
+
+    /// Caps the journal at `max_edits` recorded voxel changes, the oldest transactions are dropped
+    /// first. A transaction with more changes than that can't be undone, and clears the journal.
+    pub fn set_history_max_edits(&mut self, max_edits: usize) {
+        self.history.max_edits = max_edits;
+        self.history.trim();
+    }
+
+    pub fn clear_history(&mut self) {
+        self.history.clear();
+    }
+
+    // ticks aren't journaled, so only voxels still holding `expected` are written
+    fn apply_edits(
+        &mut self,
+        mut edits: impl Iterator<Item = (Offset3d<i32>, Voxel, Voxel)>,
+    ) -> Result<(), WorldError> {
+        let recording = self.history.set_recording(false);
+        self.suppress_events();
+        let result = edits.try_for_each(|(position, expected, voxel)| {
+            if self.get_voxel(position) != Some(&expected) {
+                return Ok(());
+            }
+            self.set_voxel(position, voxel).map(|_| ())
+        });
+        self.resume_events();
+        self.history.set_recording(recording);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voxel::{new_test_world, Region};
+
+    #[test]
+    fn undo_and_redo_restore_voxels() {
+        let mut world = new_test_world();
+        let position = Offset3d::new(1, 2, 3);
+        world.set_voxel(position, Voxel::Tile(1)).unwrap();
+        world.set_voxel(position, Voxel::Tile(2)).unwrap();
+
+        assert_eq!(world.undo().unwrap().as_deref(), Some("edit"));
+        assert_eq!(world.get_voxel(position), Some(&Voxel::Tile(1)));
+        assert_eq!(world.undo().unwrap().as_deref(), Some("edit"));
+        assert_eq!(world.get_voxel(position), Some(&Voxel::Void));
+        assert_eq!(world.undo().unwrap(), None);
+
+        assert!(world.redo().unwrap().is_some());
+        assert!(world.redo().unwrap().is_some());
+        assert_eq!(world.get_voxel(position), Some(&Voxel::Tile(2)));
+        assert_eq!(world.redo().unwrap(), None);
+    }
+
+    #[test]
+    fn nested_transactions_are_one_step() {
+        let mut world = new_test_world();
+        world.begin_transaction("outer");
+        world
+            .set_voxel(Offset3d::new(0, 0, 0), Voxel::Tile(0))
+            .unwrap();
+        world.begin_transaction("inner");
+        world
+            .set_voxel(Offset3d::new(1, 0, 0), Voxel::Tile(1))
+            .unwrap();
+        world.commit_transaction();
+        assert!(world.is_transaction_open());
+        world.commit_transaction();
+
+        assert_eq!(world.undo().unwrap().as_deref(), Some("outer"));
+        assert_eq!(world.get_voxel(Offset3d::new(0, 0, 0)), Some(&Voxel::Void));
+        assert_eq!(world.get_voxel(Offset3d::new(1, 0, 0)), Some(&Voxel::Void));
+        assert!(!world.can_undo());
+    }
+
+    #[test]
+    fn open_transaction_blocks_undo() {
+        let mut world = new_test_world();
+        world
+            .set_voxel(Offset3d::new(0, 0, 0), Voxel::Tile(0))
+            .unwrap();
+        world.begin_transaction("open");
+        assert!(matches!(world.undo(), Err(WorldError::TransactionOpen)));
+        assert!(matches!(world.redo(), Err(WorldError::TransactionOpen)));
+    }
+
+    #[test]
+    fn new_edit_clears_redo() {
+        let mut world = new_test_world();
+        world
+            .set_voxel(Offset3d::new(0, 0, 0), Voxel::Tile(0))
+            .unwrap();
+        world.undo().unwrap();
+        assert!(world.can_redo());
+        world
+            .set_voxel(Offset3d::new(1, 0, 0), Voxel::Tile(0))
+            .unwrap();
+        assert!(!world.can_redo());
+    }
+
+    #[test]
+    fn max_edits_drops_oldest_transactions() {
+        let mut world = new_test_world();
+        world.set_history_max_edits(2);
+        for x in 0..3 {
+            world
+                .set_voxel(Offset3d::new(x, 0, 0), Voxel::Tile(0))
+                .unwrap();
+        }
+        assert!(world.undo().unwrap().is_some());
+        assert!(world.undo().unwrap().is_some());
+        assert_eq!(world.undo().unwrap(), None);
+        assert_eq!(
+            world.get_voxel(Offset3d::new(0, 0, 0)),
+            Some(&Voxel::Tile(0))
+        );
+    }
+
+    #[test]
+    fn oversized_transaction_stops_recording() {
+        let mut world = new_test_world();
+        world.set_history_max_edits(3);
+        world
+            .set_voxel(Offset3d::new(0, 1, 0), Voxel::Tile(0))
+            .unwrap();
+        world.begin_transaction("large");
+        for x in 0..4 {
+            world
+                .set_voxel(Offset3d::new(x, 0, 0), Voxel::Tile(1))
+                .unwrap();
+        }
+        assert_eq!(world.history.memory_used(), 0);
+        assert!(!world.can_undo());
+        world.commit_transaction();
+        assert!(!world.can_undo());
+
+        // the next transaction records again
+        world
+            .fill(
+                Region::new(Offset3d::new(0, 2, 0), Offset3d::new(2, 2, 0)),
+                Voxel::Tile(2),
+            )
+            .unwrap();
+        assert_eq!(world.undo().unwrap().as_deref(), Some("fill"));
+    }
+
+    #[test]
+    fn undo_skips_voxels_changed_since() {
+        let mut world = new_test_world();
+        let (a, b) = (Offset3d::new(0, 0, 0), Offset3d::new(1, 0, 0));
+        world.begin_transaction("place");
+        world.set_voxel(a, Voxel::Tile(0)).unwrap();
+        world.set_voxel(b, Voxel::Tile(0)).unwrap();
+        world.commit_transaction();
+        // stands in for a tick, which isn't recorded
+        let recording = world.history.set_recording(false);
+        world.set_voxel(b, Voxel::Tile(2)).unwrap();
+        world.history.set_recording(recording);
+
+        world.undo().unwrap();
+        assert_eq!(world.get_voxel(a), Some(&Voxel::Void));
+        assert_eq!(world.get_voxel(b), Some(&Voxel::Tile(2)));
+        world.redo().unwrap();
+        assert_eq!(world.get_voxel(a), Some(&Voxel::Tile(0)));
+        assert_eq!(world.get_voxel(b), Some(&Voxel::Tile(2)));
+    }
+}
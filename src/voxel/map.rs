@@ -0,0 +1,111 @@
+use super::{Region, Voxel, World};
+use crate::{Color, Offset3d};
+
+impl World {
+    const MAP_FLUID_OPACITY: f32 = 0.6;
+    const MAP_FALLBACK_COLORS: [Color<f32>; 4] = [
+        Color::new(0.78, 0.55, 0.31, 1.0),
+        Color::new(0.2, 0.31, 0.2, 1.0),
+        Color::new(0.63, 0.63, 0.9, 1.0),
+        Color::new(0.24, 0.39, 0.86, 1.0),
+    ];
+
+    // renders the region from above, each column becomes a `scale` sized square coloured by the
+    // average top texture colour of its highest solid voxel, and darkens with depth
+    pub fn render_map(&self, region: Region, scale: u32) -> image::RgbaImage {
+        let scale = scale.max(1);
+        let size = region.size();
+        let tile_colors: Vec<_> = (0..self.max_tiles)
+            .map(|tile_index| self.get_tile_display_color(tile_index))
+            .collect();
+        let get_shade = |y: i32| 0.5 + 0.5 * (y - region.min.y + 1) as f32 / size.height as f32;
+
+        let mut image = image::RgbaImage::new(size.width * scale, size.depth * scale);
+        for z in 0..size.depth {
+            for x in 0..size.width {
+                let column = region.min + Offset3d::new(x as i32, 0, z as i32);
+                let mut color = Color::clear();
+                let mut fluid = None;
+                for y in (region.min.y..=region.max.y).rev() {
+                    match self.get_voxel(Offset3d::new(column.x, y, column.z)) {
+                        Some(Voxel::Tile(tile_index)) => {
+                            color = tile_colors[*tile_index as usize];
+                            let shade = get_shade(y);
+                            color =
+                                Color::new(color.r * shade, color.g * shade, color.b * shade, 1.0);
+                            break;
+                        }
+                        Some(Voxel::Fluid(other)) if fluid.is_none() => {
+                            let shade = get_shade(y);
+                            let other = tile_colors[other.tile_index as usize];
+                            fluid = Some(Color::new(
+                                other.r * shade,
+                                other.g * shade,
+                                other.b * shade,
+                                1.0,
+                            ));
+                        }
+                        _ => {}
+                    }
+                }
+
+                // fluids are see-through, blend them over whatever they cover
+                if let Some(fluid) = fluid {
+                    let opacity = Self::MAP_FLUID_OPACITY;
+                    let blend = |over: f32, under: f32| over * opacity + under * (1.0 - opacity);
+                    color = if color.a > 0.0 {
+                        Color::new(
+                            blend(fluid.r, color.r),
+                            blend(fluid.g, color.g),
+                            blend(fluid.b, color.b),
+                            1.0,
+                        )
+                    } else {
+                        Color::new(fluid.r, fluid.g, fluid.b, opacity)
+                    };
+                }
+
+                let pixel = image::Rgba(
+                    [color.r, color.g, color.b, color.a]
+                        .map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8),
+                );
+                for pixel_z in 0..scale {
+                    for pixel_x in 0..scale {
+                        image.put_pixel(x * scale + pixel_x, z * scale + pixel_z, pixel);
+                    }
+                }
+            }
+        }
+        image
+    }
+
+    // the texture colour, or a fixed colour for tiles without a texture
+    pub(super) fn get_tile_display_color(&self, tile_index: u32) -> Color<f32> {
+        self.get_tile_color(tile_index).unwrap_or(
+            Self::MAP_FALLBACK_COLORS[tile_index as usize % Self::MAP_FALLBACK_COLORS.len()],
+        )
+    }
+
+    // average colour of the top face in a tile's texture
+    pub fn get_tile_color(&self, tile_index: u32) -> Option<Color<f32>> {
+        let pixels = self.tile_pixels.get(tile_index as usize)?.as_ref()?;
+        let row_size = 4 * Self::TEXTURE_SIZE.width as usize;
+        let top_size = row_size * Self::TEXTURE_SIZE.height as usize / 3;
+        let mut sum = [0u32; 3];
+        let mut count = 0u32;
+        for pixel in pixels[..top_size].chunks_exact(4) {
+            if pixel[3] == 0 {
+                continue;
+            }
+            sum.iter_mut()
+                .zip(pixel)
+                .for_each(|(sum, channel)| *sum += *channel as u32);
+            count += 1;
+        }
+        if count == 0 {
+            return None;
+        }
+        let [r, g, b] = sum.map(|sum| sum as f32 / count as f32 / 255.0);
+        Some(Color::new(r, g, b, 1.0))
+    }
+}
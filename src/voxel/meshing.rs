@@ -0,0 +1,231 @@
+use super::{Face, Faces, Region, Voxel, World};
+use crate::{
+    graphics::mesh::{Submesh, Vertex},
+    Color, Offset3d, Vector2, Vector3,
+};
+use std::collections::BTreeMap;
+
+/// CPU side geometry of a world, submesh 0 holds opaque tiles and submesh 1 translucent fluids.
+#[derive(Debug)]
+pub struct MeshData {
+    pub vertices: Vec<Vertex>,
+    pub submeshes: Vec<Submesh>,
+}
+
+// faces only merge with faces that look exactly the same
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct FaceKey {
+    tile_index: u32,
+    height_bits: u32,
+    fluid: bool,
+}
+
+impl MeshData {
+    pub const TILE_SUBMESH: usize = 0;
+    pub const FLUID_SUBMESH: usize = 1;
+
+    fn new() -> Self {
+        Self {
+            vertices: Vec::new(),
+            submeshes: vec![Submesh::new(&[]), Submesh::new(&[])],
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.submeshes
+            .iter()
+            .all(|submesh| submesh.indices.is_empty())
+    }
+
+    // appends one face stretched over `size` voxels, starting at the voxel `position`, with its
+    // texture repeated once per voxel
+    fn push_face(
+        &mut self,
+        face: Face,
+        position: Offset3d<i32>,
+        size: Offset3d<i32>,
+        key: FaceKey,
+    ) {
+        let (color, submesh) = match key.fluid {
+            true => (World::FLUID_COLOR, Self::FLUID_SUBMESH),
+            false => (Color::white(), Self::TILE_SUBMESH),
+        };
+        let height = f32::from_bits(key.height_bits);
+        let world_position = Vector3::new(position.x as f32, position.y as f32, position.z as f32);
+        let normal = {
+            let normal = face.get_voxel_normal();
+            Vector3::new(normal.x as f32, normal.y as f32, normal.z as f32)
+        };
+        let (u_size, v_size) = match face {
+            Face::PosX | Face::NegX => (size.z, size.y),
+            Face::PosY | Face::NegY => (size.x, size.z),
+            Face::PosZ | Face::NegZ => (size.x, size.y),
+        };
+        let layer = key.tile_index * World::TEXTURE_SECTIONS + face.get_texture_section();
+        let vertex_count = self.vertices.len() as u32;
+        let vertices = face
+            .get_vertex_positions()
+            .into_iter()
+            .zip(Face::get_vertex_uvs())
+            .map(|(vertex_position, uv)| {
+                let stretch = |unit: f32, size: i32| match unit > 0.0 {
+                    true => (size - 1) as f32,
+                    false => 0.0,
+                };
+                let mut offset = Vector3::new(
+                    stretch(vertex_position.x, size.x),
+                    stretch(vertex_position.y, size.y),
+                    stretch(vertex_position.z, size.z),
+                );
+                // lower the top of partially filled voxels
+                if vertex_position.y > 0.0 {
+                    offset.y += height - 1.0;
+                }
+                let position = vertex_position + offset + world_position;
+                let uv = Vector2::new(uv.x * u_size as f32, uv.y * v_size as f32);
+                Vertex::new(position, normal, color, uv, layer)
+            });
+        self.vertices.extend(vertices);
+        self.submeshes[submesh]
+            .indices
+            .extend(Face::get_indices().map(|index| vertex_count + index));
+    }
+}
+
+impl World {
+    /// Meshes the voxels in `region` as if everything outside it within the world was void.
+    /// Greedy meshing merges neighbouring faces of the same tile into larger quads, with their
+    /// texture repeated across them.
+    pub fn build_mesh(&self, region: Region, greedy: bool) -> MeshData {
+        let mut mesh_data = MeshData::new();
+        let Some(region) = region.intersection(&self.bounds()) else { return mesh_data; };
+        let bounds = self.bounds();
+        let unit_size = Offset3d::new(1, 1, 1);
+
+        // faces grouped by direction, slice and appearance, merged further below
+        let mut planes: BTreeMap<(Face, i32, FaceKey), Vec<Offset3d<i32>>> = BTreeMap::new();
+        let voxels = self
+            .voxels(region)
+            .filter(|(_, voxel)| **voxel != Voxel::Void);
+        for (position, voxel) in voxels {
+            let faces = self.get_faces(position).unwrap_or_else(Faces::empty);
+            let key = match *voxel {
+                Voxel::Void => continue,
+                Voxel::Tile(tile_index) => FaceKey {
+                    tile_index,
+                    height_bits: 1.0f32.to_bits(),
+                    fluid: false,
+                },
+                Voxel::Fluid(fluid) => FaceKey {
+                    tile_index: fluid.tile_index,
+                    height_bits: self.get_fluid_height(position, fluid).to_bits(),
+                    fluid: true,
+                },
+            };
+            for face in Face::ALL {
+                let neighbour = position + face.get_voxel_normal();
+                let visible = if !region.contains(neighbour) && bounds.contains(neighbour) {
+                    voxel.is_face_visible(face, Voxel::Void)
+                } else {
+                    faces.contains(face.into())
+                };
+                if !visible {
+                    continue;
+                }
+                if greedy {
+                    let slice = get_axes(face).0;
+                    planes
+                        .entry((face, get_axis(position, slice), key))
+                        .or_default()
+                        .push(position);
+                } else {
+                    mesh_data.push_face(face, position, unit_size, key);
+                }
+            }
+        }
+
+        for ((face, _, key), positions) in planes {
+            for (position, size) in merge_faces(face, &positions) {
+                mesh_data.push_face(face, position, size, key);
+            }
+        }
+        mesh_data
+    }
+}
+
+// the axis a face looks along, followed by the two axes of its plane
+#[inline]
+fn get_axes(face: Face) -> (usize, usize, usize) {
+    match face {
+        Face::PosX | Face::NegX => (0, 1, 2),
+        Face::PosY | Face::NegY => (1, 0, 2),
+        Face::PosZ | Face::NegZ => (2, 0, 1),
+    }
+}
+
+#[inline]
+fn get_axis(position: Offset3d<i32>, axis: usize) -> i32 {
+    [position.x, position.y, position.z][axis]
+}
+
+// greedily covers faces within one plane with rectangles, returning each rectangle's minimum
+// voxel and its size in voxels
+fn merge_faces(face: Face, positions: &[Offset3d<i32>]) -> Vec<(Offset3d<i32>, Offset3d<i32>)> {
+    let (_, u_axis, v_axis) = get_axes(face);
+    let (mut u_min, mut v_min) = (i32::MAX, i32::MAX);
+    let (mut u_max, mut v_max) = (i32::MIN, i32::MIN);
+    for position in positions {
+        let (u, v) = (get_axis(*position, u_axis), get_axis(*position, v_axis));
+        (u_min, u_max) = (u_min.min(u), u_max.max(u));
+        (v_min, v_max) = (v_min.min(v), v_max.max(v));
+    }
+    let width = (u_max - u_min + 1) as usize;
+    let height = (v_max - v_min + 1) as usize;
+    let mut mask = vec![false; width * height];
+    for position in positions {
+        let u = (get_axis(*position, u_axis) - u_min) as usize;
+        let v = (get_axis(*position, v_axis) - v_min) as usize;
+        mask[v * width + u] = true;
+    }
+
+    let mut rectangles = Vec::new();
+    for v in 0..height {
+        let mut u = 0;
+        while u < width {
+            if !mask[v * width + u] {
+                u += 1;
+                continue;
+            }
+            let mut quad_width = 1;
+            while u + quad_width < width && mask[v * width + u + quad_width] {
+                quad_width += 1;
+            }
+            let mut quad_height = 1;
+            while v + quad_height < height
+                && mask[(v + quad_height) * width + u..][..quad_width]
+                    .iter()
+                    .all(|filled| *filled)
+            {
+                quad_height += 1;
+            }
+            for row in v..v + quad_height {
+                mask[row * width + u..][..quad_width].fill(false);
+            }
+
+            // back to world space, the slice axis comes from any of the merged faces
+            let mut position = [0; 3];
+            let mut size = [1; 3];
+            position[get_axes(face).0] = get_axis(positions[0], get_axes(face).0);
+            position[u_axis] = u_min + u as i32;
+            position[v_axis] = v_min + v as i32;
+            size[u_axis] = quad_width as i32;
+            size[v_axis] = quad_height as i32;
+            rectangles.push((
+                Offset3d::new(position[0], position[1], position[2]),
+                Offset3d::new(size[0], size[1], size[2]),
+            ));
+            u += quad_width;
+        }
+    }
+    rectangles
+}
@@ -0,0 +1,158 @@
+use super::FileError;
+use std::{collections::HashMap, io::Read};
+
+// the subset of named binary tags needed to read schematics, all values are big-endian
+#[derive(Clone, Debug, PartialEq)]
+pub(super) enum Tag {
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    ByteArray(Vec<u8>),
+    String(String),
+    List(Vec<Tag>),
+    Compound(HashMap<String, Tag>),
+    IntArray(Vec<i32>),
+    LongArray(Vec<i64>),
+}
+
+impl Tag {
+    // nesting deeper than this is treated as corrupt data rather than risking the stack
+    const MAX_DEPTH: u32 = 512;
+
+    // reads the unnamed root compound, returning its name as well
+    pub(super) fn read_root<R: Read>(reader: &mut R) -> Result<(String, Self), FileError> {
+        match read_u8(reader)? {
+            10 => {
+                let name = read_string(reader)?;
+                Ok((name, Self::read_payload(reader, 10, 0)?))
+            }
+            _ => Err(FileError::DataInvalid),
+        }
+    }
+
+    pub(super) fn get(&self, name: &str) -> Option<&Self> {
+        match self {
+            Self::Compound(tags) => tags.get(name),
+            _ => None,
+        }
+    }
+
+    // any integer tag widened to i64
+    pub(super) fn as_integer(&self) -> Option<i64> {
+        match *self {
+            Self::Byte(value) => Some(value as i64),
+            Self::Short(value) => Some(value as i64),
+            Self::Int(value) => Some(value as i64),
+            Self::Long(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub(super) fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Self::ByteArray(bytes) => Some(bytes),
+            _ => None,
+        }
+    }
+
+    pub(super) fn as_compound(&self) -> Option<&HashMap<String, Self>> {
+        match self {
+            Self::Compound(tags) => Some(tags),
+            _ => None,
+        }
+    }
+
+    fn read_payload<R: Read>(reader: &mut R, id: u8, depth: u32) -> Result<Self, FileError> {
+        if depth > Self::MAX_DEPTH {
+            return Err(FileError::DataInvalid);
+        }
+        let tag = match id {
+            1 => Self::Byte(read_u8(reader)? as i8),
+            2 => Self::Short(i16::from_be_bytes(read_array(reader)?)),
+            3 => Self::Int(i32::from_be_bytes(read_array(reader)?)),
+            4 => Self::Long(i64::from_be_bytes(read_array(reader)?)),
+            5 => Self::Float(f32::from_be_bytes(read_array(reader)?)),
+            6 => Self::Double(f64::from_be_bytes(read_array(reader)?)),
+            7 => {
+                let length = read_length(reader)?;
+                let mut bytes = Vec::new();
+                reader.take(length as u64).read_to_end(&mut bytes)?;
+                if bytes.len() != length {
+                    return Err(FileError::DataInvalid);
+                }
+                Self::ByteArray(bytes)
+            }
+            8 => Self::String(read_string(reader)?),
+            9 => {
+                let element_id = read_u8(reader)?;
+                let length = read_length(reader)?;
+                if element_id == 0 && length > 0 {
+                    return Err(FileError::DataInvalid);
+                }
+                let mut tags = Vec::new();
+                for _ in 0..length {
+                    tags.push(Self::read_payload(reader, element_id, depth + 1)?);
+                }
+                Self::List(tags)
+            }
+            10 => {
+                let mut tags = HashMap::new();
+                loop {
+                    let element_id = read_u8(reader)?;
+                    if element_id == 0 {
+                        break;
+                    }
+                    let name = read_string(reader)?;
+                    tags.insert(name, Self::read_payload(reader, element_id, depth + 1)?);
+                }
+                Self::Compound(tags)
+            }
+            11 => {
+                let length = read_length(reader)?;
+                let mut values = Vec::new();
+                for _ in 0..length {
+                    values.push(i32::from_be_bytes(read_array(reader)?));
+                }
+                Self::IntArray(values)
+            }
+            12 => {
+                let length = read_length(reader)?;
+                let mut values = Vec::new();
+                for _ in 0..length {
+                    values.push(i64::from_be_bytes(read_array(reader)?));
+                }
+                Self::LongArray(values)
+            }
+            _ => return Err(FileError::DataInvalid),
+        };
+        Ok(tag)
+    }
+}
+
+#[inline]
+fn read_u8<R: Read>(reader: &mut R) -> Result<u8, FileError> {
+    Ok(read_array::<R, 1>(reader)?[0])
+}
+
+#[inline]
+fn read_array<R: Read, const N: usize>(reader: &mut R) -> Result<[u8; N], FileError> {
+    let mut bytes = [0; N];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn read_length<R: Read>(reader: &mut R) -> Result<usize, FileError> {
+    let length = i32::from_be_bytes(read_array(reader)?);
+    usize::try_from(length).map_err(|_| FileError::DataInvalid)
+}
+
+// java's modified utf-8, which only differs from utf-8 for characters no block name uses
+fn read_string<R: Read>(reader: &mut R) -> Result<String, FileError> {
+    let length = u16::from_be_bytes(read_array(reader)?) as usize;
+    let mut bytes = vec![0; length];
+    reader.read_exact(&mut bytes)?;
+    String::from_utf8(bytes).map_err(|_| FileError::DataInvalid)
+}
@@ -0,0 +1,602 @@
+use super::{Face, Region, Voxel, World, WorldEvent};
+use crate::{Offset3d, Vector3};
+use std::{array, mem};
+
+/// A sparse voxel octree over a cube of voxels, where uniform parts of the cube are stored as a
+/// single node. Positions inside the cube but outside the region it was built from are void.
+///
+/// It is kept in sync with its world by feeding it the world's events:
+/// `for event in receiver.drain() { octree.update(&world, &event); }`
+#[derive(Clone, Debug)]
+pub struct Octree {
+    region: Region,
+    min: Offset3d<i32>,
+    depth: u32,
+    // the root is node 0, children are stored in blocks of 8 ordered by x, then y, then z
+    nodes: Vec<Node>,
+    free_blocks: Vec<u32>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RayHit {
+    pub position: Offset3d<i32>,
+    pub voxel: Voxel,
+    /// The face the ray entered through, `None` if it started inside the voxel.
+    pub face: Option<Face>,
+    pub distance: f32,
+}
+
+// a leaf holds its voxel, a branch the majority voxel of its children for level of detail
+#[derive(Clone, Copy, Debug)]
+struct Node {
+    voxel: Voxel,
+    children: u32,
+}
+
+impl Node {
+    const LEAF: u32 = 0;
+
+    fn leaf(voxel: Voxel) -> Self {
+        Self {
+            voxel,
+            children: Self::LEAF,
+        }
+    }
+
+    fn is_leaf(&self) -> bool {
+        self.children == Self::LEAF
+    }
+}
+
+impl Octree {
+    pub fn region(&self) -> Region {
+        self.region
+    }
+
+    // the side length of the cube, a power of two
+    pub fn side(&self) -> u32 {
+        1 << self.depth
+    }
+
+    pub fn depth(&self) -> u32 {
+        self.depth
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.nodes.len() - self.free_blocks.len() * 8
+    }
+
+    pub fn memory_used(&self) -> usize {
+        self.nodes.capacity() * mem::size_of::<Node>()
+            + self.free_blocks.capacity() * mem::size_of::<u32>()
+    }
+
+    pub fn get_voxel(&self, position: Offset3d<i32>) -> Option<Voxel> {
+        self.region
+            .contains(position)
+            .then(|| self.find_leaf(position).0)
+    }
+
+    /// Writes a voxel, splitting and collapsing nodes as needed. Returns `false` for positions
+    /// outside the region.
+    pub fn set_voxel(&mut self, position: Offset3d<i32>, voxel: Voxel) -> bool {
+        if !self.region.contains(position) {
+            return false;
+        }
+        self.set_recursive(0, self.min, self.side(), position, voxel);
+        true
+    }
+
+    /// Applies one of the world's events, voxels changed outside the region are ignored.
+    pub fn update(&mut self, world: &World, event: &WorldEvent) {
+        match *event {
+            WorldEvent::VoxelChanged {
+                position,
+                new_voxel,
+                ..
+            } => {
+                self.set_voxel(position, new_voxel);
+            }
+            WorldEvent::RegionChanged(region) => {
+                let Some(region) = region.intersection(&self.region) else { return; };
+                for position in region.positions() {
+                    let voxel = world.get_voxel(position).copied().unwrap_or_default();
+                    self.set_voxel(position, voxel);
+                }
+            }
+            WorldEvent::GenerationFinished { .. } => {}
+        }
+    }
+
+    /// The voxel representing `position` at a level of detail, level 0 is the voxel itself and
+    /// every level above halves the resolution, down to the whole cube at [`Octree::depth`].
+    pub fn sample(&self, position: Offset3d<i32>, level: u32) -> Option<Voxel> {
+        if !self.region.contains(position) {
+            return None;
+        }
+        let mut index = 0;
+        let mut min = self.min;
+        let mut side = self.side();
+        loop {
+            let node = self.nodes[index];
+            if node.is_leaf() || side.trailing_zeros() <= level {
+                return Some(node.voxel);
+            }
+            side /= 2;
+            let child = get_child_index(min, side, position);
+            min = get_child_min(min, side, child);
+            index = node.children as usize + child;
+        }
+    }
+
+    /// Every non void node at a level of detail as the region it covers and its voxel, nodes that
+    /// are uniform above that level are returned whole.
+    pub fn get_lod_nodes(&self, level: u32) -> Vec<(Region, Voxel)> {
+        let mut lod_nodes = Vec::new();
+        let mut stack = vec![(0, self.min, self.side())];
+        while let Some((index, min, side)) = stack.pop() {
+            let node = self.nodes[index];
+            if node.is_leaf() || side.trailing_zeros() <= level {
+                if node.voxel != Voxel::Void {
+                    lod_nodes.push((get_node_region(min, side), node.voxel));
+                }
+                continue;
+            }
+            let side = side / 2;
+            for child in 0..8 {
+                let child_min = get_child_min(min, side, child);
+                stack.push((node.children as usize + child, child_min, side));
+            }
+        }
+        lod_nodes
+    }
+
+    /// Walks the ray through the cube, skipping empty nodes whole, and returns the first non void
+    /// voxel within `max_distance`. Voxels are centred on their position like the world's mesh,
+    /// spanning half a voxel to either side.
+    pub fn raycast(
+        &self,
+        origin: Vector3<f32>,
+        direction: Vector3<f32>,
+        max_distance: f32,
+    ) -> Option<RayHit> {
+        let length =
+            (direction.x * direction.x + direction.y * direction.y + direction.z * direction.z)
+                .sqrt();
+        if length == 0.0 {
+            return None;
+        }
+        // shifted so every voxel spans from its position to the next integer
+        let origin = [origin.x + 0.5, origin.y + 0.5, origin.z + 0.5];
+        let direction = [
+            direction.x / length,
+            direction.y / length,
+            direction.z / length,
+        ];
+        let cube_min = [self.min.x, self.min.y, self.min.z];
+        let side = self.side() as i64;
+
+        // where the ray enters the cube
+        let mut t_enter = f32::NEG_INFINITY;
+        let mut t_exit = f32::INFINITY;
+        let mut enter_axis = None;
+        for axis in 0..3 {
+            let low = cube_min[axis] as f32;
+            let high = (cube_min[axis] as i64 + side) as f32;
+            if direction[axis] == 0.0 {
+                if origin[axis] < low || origin[axis] >= high {
+                    return None;
+                }
+                continue;
+            }
+            let (mut t0, mut t1) = (
+                (low - origin[axis]) / direction[axis],
+                (high - origin[axis]) / direction[axis],
+            );
+            if t0 > t1 {
+                mem::swap(&mut t0, &mut t1);
+            }
+            if t0 > t_enter {
+                (t_enter, enter_axis) = (t0, Some(axis));
+            }
+            t_exit = t_exit.min(t1);
+        }
+        if t_enter > t_exit || t_exit < 0.0 {
+            return None;
+        }
+
+        let clamp_to_cube = |axis: usize, value: f32| {
+            (value.floor() as i64).clamp(cube_min[axis] as i64, cube_min[axis] as i64 + side - 1)
+        };
+        let mut t = t_enter.max(0.0);
+        let mut cell: [i64; 3] = array::from_fn(|axis| clamp_to_cube(axis, origin[axis]));
+        let mut face = None;
+        if let (Some(axis), true) = (enter_axis, t_enter > 0.0) {
+            for (other, value) in cell.iter_mut().enumerate() {
+                *value = clamp_to_cube(other, origin[other] + direction[other] * t);
+            }
+            cell[axis] = match direction[axis] > 0.0 {
+                true => cube_min[axis] as i64,
+                false => cube_min[axis] as i64 + side - 1,
+            };
+            face = Some(get_entry_face(axis, direction[axis]));
+        }
+
+        while t <= max_distance {
+            let position = Offset3d::new(cell[0] as i32, cell[1] as i32, cell[2] as i32);
+            let (voxel, node_min, node_side) = self.find_leaf(position);
+            if voxel != Voxel::Void && self.region.contains(position) {
+                return Some(RayHit {
+                    position,
+                    voxel,
+                    face,
+                    distance: t,
+                });
+            }
+
+            // leave the node through the nearest of its far planes
+            let node_min = [node_min.x as i64, node_min.y as i64, node_min.z as i64];
+            let node_side = node_side as i64;
+            let mut exit = (f32::INFINITY, 0);
+            for axis in 0..3 {
+                if direction[axis] == 0.0 {
+                    continue;
+                }
+                let bound = match direction[axis] > 0.0 {
+                    true => node_min[axis] + node_side,
+                    false => node_min[axis],
+                };
+                let t_axis = (bound as f32 - origin[axis]) / direction[axis];
+                if t_axis < exit.0 {
+                    exit = (t_axis, axis);
+                }
+            }
+            let (t_axis, axis) = exit;
+            t = t_axis.max(t);
+            for other in 0..3 {
+                cell[other] = (((origin[other] + direction[other] * t).floor()) as i64)
+                    .clamp(node_min[other], node_min[other] + node_side - 1);
+            }
+            cell[axis] = match direction[axis] > 0.0 {
+                true => node_min[axis] + node_side,
+                false => node_min[axis] - 1,
+            };
+            if cell[axis] < cube_min[axis] as i64 || cell[axis] >= cube_min[axis] as i64 + side {
+                return None;
+            }
+            face = Some(get_entry_face(axis, direction[axis]));
+        }
+        None
+    }
+
+    // the leaf containing a position inside the cube, with its minimum corner and side length
+    fn find_leaf(&self, position: Offset3d<i32>) -> (Voxel, Offset3d<i32>, u32) {
+        let mut index = 0;
+        let mut min = self.min;
+        let mut side = self.side();
+        loop {
+            let node = self.nodes[index];
+            if node.is_leaf() {
+                return (node.voxel, min, side);
+            }
+            side /= 2;
+            let child = get_child_index(min, side, position);
+            min = get_child_min(min, side, child);
+            index = node.children as usize + child;
+        }
+    }
+
+    fn build(&mut self, world: &World, min: Offset3d<i32>, side: u32) -> Node {
+        let Some(region) = get_node_region(min, side).intersection(&self.region) else { return Node::leaf(Voxel::Void); };
+        if side == 1 {
+            return Node::leaf(world.get_voxel(region.min).copied().unwrap_or_default());
+        }
+        let side = side / 2;
+        let children: [Node; 8] =
+            array::from_fn(|child| self.build(world, get_child_min(min, side, child), side));
+        self.join(children)
+    }
+
+    fn set_recursive(
+        &mut self,
+        index: usize,
+        min: Offset3d<i32>,
+        side: u32,
+        position: Offset3d<i32>,
+        voxel: Voxel,
+    ) {
+        let node = self.nodes[index];
+        if node.is_leaf() {
+            if node.voxel == voxel {
+                return;
+            }
+            if side == 1 {
+                self.nodes[index] = Node::leaf(voxel);
+                return;
+            }
+        }
+
+        // split a uniform node so only the containing child changes
+        let children = match node.is_leaf() {
+            true => self.allocate([Node::leaf(node.voxel); 8]),
+            false => node.children,
+        };
+        let side = side / 2;
+        let child = get_child_index(min, side, position);
+        let child_min = get_child_min(min, side, child);
+        self.set_recursive(children as usize + child, child_min, side, position, voxel);
+
+        let block = children as usize..children as usize + 8;
+        let nodes: [Node; 8] = self.nodes[block].try_into().unwrap();
+        self.nodes[index] = match get_uniform_voxel(&nodes) {
+            Some(voxel) => {
+                self.free_blocks.push(children);
+                Node::leaf(voxel)
+            }
+            None => Node {
+                voxel: get_majority(&nodes),
+                children,
+            },
+        };
+    }
+
+    // collapses uniform children into a leaf, otherwise stores them under a new branch
+    fn join(&mut self, children: [Node; 8]) -> Node {
+        match get_uniform_voxel(&children) {
+            Some(voxel) => Node::leaf(voxel),
+            None => Node {
+                voxel: get_majority(&children),
+                children: self.allocate(children),
+            },
+        }
+    }
+
+    fn allocate(&mut self, children: [Node; 8]) -> u32 {
+        match self.free_blocks.pop() {
+            Some(block) => {
+                self.nodes[block as usize..][..8].copy_from_slice(&children);
+                block
+            }
+            None => {
+                let block = self.nodes.len() as u32;
+                self.nodes.extend_from_slice(&children);
+                block
+            }
+        }
+    }
+}
+
+impl World {
+    /// Builds an octree from the voxels in `region` clamped to the world. `None` if the region
+    /// lies outside the world.
+    pub fn build_octree(&self, region: Region) -> Option<Octree> {
+        let region = region.intersection(&self.bounds())?;
+        let size = region.size();
+        let side = size
+            .width
+            .max(size.height)
+            .max(size.depth)
+            .next_power_of_two();
+        let mut octree = Octree {
+            region,
+            min: region.min,
+            depth: side.trailing_zeros(),
+            nodes: vec![Node::leaf(Voxel::Void)],
+            free_blocks: Vec::new(),
+        };
+        octree.nodes[0] = octree.build(self, region.min, side);
+        Some(octree)
+    }
+}
+
+// clamped to the largest position, cubes may reach past it
+#[inline]
+fn get_node_region(min: Offset3d<i32>, side: u32) -> Region {
+    let get_max = |min: i32| (min as i64 + side as i64 - 1).min(i32::MAX as i64) as i32;
+    Region::new(
+        min,
+        Offset3d::new(get_max(min.x), get_max(min.y), get_max(min.z)),
+    )
+}
+
+#[inline]
+fn get_child_index(min: Offset3d<i32>, child_side: u32, position: Offset3d<i32>) -> usize {
+    let get_bit =
+        |value: i32, min: i32| ((value as i64 - min as i64) >= child_side as i64) as usize;
+    get_bit(position.x, min.x) | get_bit(position.y, min.y) << 1 | get_bit(position.z, min.z) << 2
+}
+
+#[inline]
+fn get_child_min(min: Offset3d<i32>, child_side: u32, child: usize) -> Offset3d<i32> {
+    let get_offset = |bit: usize| (child >> bit & 1) as i32 * child_side as i32;
+    min + Offset3d::new(get_offset(0), get_offset(1), get_offset(2))
+}
+
+// the face of a voxel a ray moving along `axis` enters through
+#[inline]
+fn get_entry_face(axis: usize, direction: f32) -> Face {
+    match (axis, direction > 0.0) {
+        (0, true) => Face::NegX,
+        (0, false) => Face::PosX,
+        (1, true) => Face::NegY,
+        (1, false) => Face::PosY,
+        (2, true) => Face::NegZ,
+        _ => Face::PosZ,
+    }
+}
+
+fn get_uniform_voxel(children: &[Node; 8]) -> Option<Voxel> {
+    let voxel = children[0].voxel;
+    children
+        .iter()
+        .all(|child| child.is_leaf() && child.voxel == voxel)
+        .then_some(voxel)
+}
+
+// the most common voxel among the children, ties favour non void voxels
+fn get_majority(children: &[Node; 8]) -> Voxel {
+    let mut counts: Vec<(Voxel, usize)> = Vec::with_capacity(8);
+    for child in children {
+        match counts.iter_mut().find(|(voxel, _)| *voxel == child.voxel) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((child.voxel, 1)),
+        }
+    }
+    counts
+        .into_iter()
+        .max_by_key(|(voxel, count)| (*count, *voxel != Voxel::Void))
+        .map_or(Voxel::Void, |(voxel, _)| voxel)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voxel::new_test_world;
+
+    #[test]
+    fn build_matches_world() {
+        let mut world = new_test_world();
+        world
+            .set_voxel(Offset3d::new(1, 2, 3), Voxel::Tile(1))
+            .unwrap();
+        world
+            .set_voxel(Offset3d::new(-4, -4, -4), Voxel::Tile(2))
+            .unwrap();
+        let octree = world.build_octree(world.bounds()).unwrap();
+        assert_eq!(octree.side(), 8);
+        for position in world.bounds().positions() {
+            assert_eq!(
+                octree.get_voxel(position).as_ref(),
+                world.get_voxel(position)
+            );
+        }
+        assert_eq!(octree.get_voxel(Offset3d::new(4, 0, 0)), None);
+        assert!(world
+            .build_octree(Region::new(
+                Offset3d::new(10, 10, 10),
+                Offset3d::new(12, 12, 12)
+            ))
+            .is_none());
+    }
+
+    #[test]
+    fn set_splits_and_collapses() {
+        let world = new_test_world();
+        let mut octree = world.build_octree(world.bounds()).unwrap();
+        assert_eq!(octree.node_count(), 1);
+
+        let position = Offset3d::new(0, 0, 0);
+        assert!(octree.set_voxel(position, Voxel::Tile(0)));
+        assert_eq!(octree.get_voxel(position), Some(Voxel::Tile(0)));
+        assert_eq!(octree.node_count(), 1 + 8 * octree.depth() as usize);
+
+        assert!(octree.set_voxel(position, Voxel::Void));
+        assert_eq!(octree.node_count(), 1);
+
+        for position in octree.region().positions() {
+            octree.set_voxel(position, Voxel::Tile(1));
+        }
+        assert_eq!(octree.node_count(), 1);
+        assert_eq!(
+            octree.sample(position, octree.depth()),
+            Some(Voxel::Tile(1))
+        );
+        assert!(!octree.set_voxel(Offset3d::new(0, 100, 0), Voxel::Tile(0)));
+    }
+
+    #[test]
+    fn update_follows_events() {
+        let mut world = new_test_world();
+        let receiver = world.subscribe();
+        let mut octree = world.build_octree(world.bounds()).unwrap();
+        world
+            .set_voxel(Offset3d::new(1, 1, 1), Voxel::Tile(2))
+            .unwrap();
+        world
+            .fill(
+                Region::new(Offset3d::new(-4, -4, -4), Offset3d::new(-3, -3, -3)),
+                Voxel::Tile(1),
+            )
+            .unwrap();
+        for event in receiver.drain() {
+            octree.update(&world, &event);
+        }
+        for position in world.bounds().positions() {
+            assert_eq!(
+                octree.get_voxel(position).as_ref(),
+                world.get_voxel(position)
+            );
+        }
+    }
+
+    #[test]
+    fn raycast_hits_first_voxel() {
+        let mut world = new_test_world();
+        world
+            .set_voxel(Offset3d::new(2, 0, 0), Voxel::Tile(1))
+            .unwrap();
+        world
+            .set_voxel(Offset3d::new(3, 0, 0), Voxel::Tile(2))
+            .unwrap();
+        let octree = world.build_octree(world.bounds()).unwrap();
+
+        let origin = Vector3::new(-3.0, 0.0, 0.0);
+        let hit = octree
+            .raycast(origin, Vector3::new(1.0, 0.0, 0.0), 100.0)
+            .unwrap();
+        assert_eq!(hit.position, Offset3d::new(2, 0, 0));
+        assert_eq!(hit.voxel, Voxel::Tile(1));
+        assert_eq!(hit.face, Some(Face::NegX));
+        assert!((hit.distance - 4.5).abs() < 1e-4);
+
+        assert!(octree
+            .raycast(origin, Vector3::new(1.0, 0.0, 0.0), 4.0)
+            .is_none());
+        assert!(octree
+            .raycast(origin, Vector3::new(0.0, 1.0, 0.0), 100.0)
+            .is_none());
+        assert!(octree
+            .raycast(origin, Vector3::new(0.0, 0.0, 0.0), 100.0)
+            .is_none());
+        let inside = octree
+            .raycast(
+                Vector3::new(2.0, 0.0, 0.0),
+                Vector3::new(0.0, 1.0, 0.0),
+                100.0,
+            )
+            .unwrap();
+        assert_eq!(inside.face, None);
+    }
+
+    #[test]
+    fn raycast_hits_near_voxel_edges() {
+        let mut world = new_test_world();
+        world
+            .set_voxel(Offset3d::new(2, 0, 0), Voxel::Tile(1))
+            .unwrap();
+        let octree = world.build_octree(world.bounds()).unwrap();
+        let direction = Vector3::new(1.0, 0.0, 0.0);
+
+        // the voxel spans half a voxel around its position, like its rendered faces
+        for offset in [-0.49, 0.49] {
+            let origin = Vector3::new(-3.0, offset, offset);
+            let hit = octree.raycast(origin, direction, 100.0).unwrap();
+            assert_eq!(hit.position, Offset3d::new(2, 0, 0));
+            assert!((hit.distance - 4.5).abs() < 1e-4);
+        }
+        for offset in [-0.51, 0.51] {
+            let origin = Vector3::new(-3.0, offset, 0.0);
+            assert!(octree.raycast(origin, direction, 100.0).is_none());
+        }
+        let inside = octree
+            .raycast(Vector3::new(1.6, 0.0, 0.0), direction, 100.0)
+            .unwrap();
+        assert_eq!(inside.position, Offset3d::new(2, 0, 0));
+        assert_eq!(inside.face, None);
+        let before = octree
+            .raycast(Vector3::new(1.4, 0.0, 0.0), direction, 100.0)
+            .unwrap();
+        assert_eq!(before.face, Some(Face::NegX));
+        assert!((before.distance - 0.1).abs() < 1e-4);
+    }
+}
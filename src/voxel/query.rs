@@ -0,0 +1,186 @@
+use super::{Face, Faces, Region, Voxel, VoxelData, World};
+use crate::Offset3d;
+use std::collections::VecDeque;
+
+/// Iterates the voxels of a region clamped to the world, x fastest, then y, then z.
+pub struct RegionIterator<'a> {
+    world: &'a World,
+    region: Option<Region>,
+    position: Offset3d<i32>,
+}
+
+/// Iterates the voxels of a region that have at least one visible face, along with those faces.
+pub struct SurfaceIterator<'a> {
+    voxels: RegionIterator<'a>,
+}
+
+impl<'a> RegionIterator<'a> {
+    fn new(world: &'a World, region: Region) -> Self {
+        let region = region.intersection(&world.bounds());
+        Self {
+            world,
+            region,
+            position: region.map_or(Offset3d::new(0, 0, 0), |region| region.min),
+        }
+    }
+
+    fn next_data(&mut self) -> Option<(Offset3d<i32>, &'a VoxelData)> {
+        let region = self.region?;
+        let position = self.position;
+        if position.x < region.max.x {
+            self.position.x += 1;
+        } else if position.y < region.max.y {
+            self.position = Offset3d::new(region.min.x, position.y + 1, position.z);
+        } else if position.z < region.max.z {
+            self.position = Offset3d::new(region.min.x, region.min.y, position.z + 1);
+        } else {
+            self.region = None;
+        }
+        let index = unsafe { self.world.get_voxel_index_unchecked(position) };
+        Some((position, &self.world.voxel_data[index]))
+    }
+}
+
+impl<'a> Iterator for RegionIterator<'a> {
+    type Item = (Offset3d<i32>, &'a Voxel);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_data()
+            .map(|(position, voxel_data)| (position, &voxel_data.voxel))
+    }
+}
+
+impl<'a> Iterator for SurfaceIterator<'a> {
+    type Item = (Offset3d<i32>, &'a Voxel, Faces);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (position, voxel_data) = self.voxels.next_data()?;
+            if !voxel_data.faces.is_empty() {
+                return Some((position, &voxel_data.voxel, voxel_data.faces));
+            }
+        }
+    }
+}
+
+impl World {
+    pub fn voxels(&self, region: Region) -> RegionIterator<'_> {
+        RegionIterator::new(self, region)
+    }
+
+    pub fn non_void_voxels(
+        &self,
+        region: Region,
+    ) -> impl Iterator<Item = (Offset3d<i32>, &Voxel)> + '_ {
+        self.voxels(region)
+            .filter(|(_, voxel)| **voxel != Voxel::Void)
+    }
+
+    pub fn surface_voxels(&self, region: Region) -> SurfaceIterator<'_> {
+        SurfaceIterator {
+            voxels: RegionIterator::new(self, region),
+        }
+    }
+
+    // the faces of a voxel that are visible and meshed
+    pub fn get_faces(&self, position: Offset3d<i32>) -> Option<Faces> {
+        self.get_voxel_data(position)
+            .map(|voxel_data| voxel_data.faces)
+    }
+
+    // the highest tile in the column, fluids are not solid
+    pub fn get_highest_voxel(&self, x: i32, z: i32) -> Option<Offset3d<i32>> {
+        let bounds = self.bounds();
+        (bounds.min.y..=bounds.max.y)
+            .rev()
+            .map(|y| Offset3d::new(x, y, z))
+            .find(|position| matches!(self.get_voxel(*position), Some(Voxel::Tile(_))))
+    }
+
+    // the neighbours across each face that lie inside the world
+    pub fn get_neighbours(
+        &self,
+        position: Offset3d<i32>,
+    ) -> impl Iterator<Item = (Face, Offset3d<i32>, &Voxel)> + '_ {
+        Face::ALL.into_iter().filter_map(move |face| {
+            let neighbour = position + face.get_voxel_normal();
+            Some((face, neighbour, self.get_voxel(neighbour)?))
+        })
+    }
+
+    /// Collects every position connected to `start` through faces where `predicate` holds,
+    /// breadth first. Empty if `start` itself doesn't match.
+    pub fn flood_fill<P>(&self, start: Offset3d<i32>, mut predicate: P) -> Vec<Offset3d<i32>>
+    where
+        P: FnMut(Offset3d<i32>, &Voxel) -> bool,
+    {
+        let mut filled = Vec::new();
+        let Some(start_index) = self.get_voxel_index(start) else { return filled; };
+        if !predicate(start, &self.voxel_data[start_index].voxel) {
+            return filled;
+        }
+        let mut visited = vec![false; self.voxel_data.len()];
+        visited[start_index] = true;
+        let mut queue = VecDeque::from([start]);
+        while let Some(position) = queue.pop_front() {
+            filled.push(position);
+            for face in Face::ALL {
+                let neighbour = position + face.get_voxel_normal();
+                let Some(index) = self.get_voxel_index(neighbour) else { continue; };
+                if visited[index] {
+                    continue;
+                }
+                visited[index] = true;
+                if predicate(neighbour, &self.voxel_data[index].voxel) {
+                    queue.push_back(neighbour);
+                }
+            }
+        }
+        filled
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voxel::new_test_world;
+
+    #[test]
+    fn flood_fill_stays_inside_walls() {
+        let mut world = new_test_world();
+        // a closed 3x3x3 box with a single void voxel inside
+        let shell = Region::new(Offset3d::new(-1, -1, -1), Offset3d::new(1, 1, 1));
+        world.fill(shell, Voxel::Tile(0)).unwrap();
+        world
+            .set_voxel(Offset3d::new(0, 0, 0), Voxel::Void)
+            .unwrap();
+
+        let inside = world.flood_fill(Offset3d::new(0, 0, 0), |_, voxel| *voxel == Voxel::Void);
+        assert_eq!(inside, vec![Offset3d::new(0, 0, 0)]);
+        let outside = world.flood_fill(Offset3d::new(-4, -4, -4), |_, voxel| *voxel == Voxel::Void);
+        assert_eq!(outside.len(), 8 * 8 * 8 - 27);
+        assert!(!outside.contains(&Offset3d::new(0, 0, 0)));
+    }
+
+    #[test]
+    fn flood_fill_visits_breadth_first_without_duplicates() {
+        let world = new_test_world();
+        let start = Offset3d::new(0, 0, 0);
+        let filled = world.flood_fill(start, |position, _| position.y == 0 && position.z == 0);
+        assert_eq!(filled.len(), 8);
+        assert_eq!(filled[0], start);
+        let distances: Vec<_> = filled.iter().map(|position| position.x.abs()).collect();
+        assert!(distances.windows(2).all(|pair| pair[0] <= pair[1]));
+    }
+
+    #[test]
+    fn flood_fill_rejects_start() {
+        let world = new_test_world();
+        assert!(world
+            .flood_fill(Offset3d::new(0, 0, 0), |_, voxel| *voxel != Voxel::Void)
+            .is_empty());
+        assert!(world
+            .flood_fill(Offset3d::new(100, 0, 0), |_, _| true)
+            .is_empty());
+    }
+}
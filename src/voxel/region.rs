@@ -0,0 +1,113 @@
+use crate::{Extent3d, Offset3d};
+
+/// An inclusive box of voxel positions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Region {
+    pub min: Offset3d<i32>,
+    pub max: Offset3d<i32>,
+}
+
+impl Region {
+    pub fn new(a: Offset3d<i32>, b: Offset3d<i32>) -> Self {
+        Self {
+            min: Offset3d::new(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z)),
+            max: Offset3d::new(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z)),
+        }
+    }
+
+    pub fn from_size(min: Offset3d<i32>, size: Extent3d<u32>) -> Self {
+        let max = min
+            + Offset3d::new(
+                size.width as i32 - 1,
+                size.height as i32 - 1,
+                size.depth as i32 - 1,
+            );
+        Self { min, max }
+    }
+
+    // `size` centred on the origin, odd sizes leave the extra voxel on the positive side
+    pub fn centered(size: Extent3d<u32>) -> Self {
+        let min = Offset3d::new(
+            -(size.width as i64 / 2) as i32,
+            -(size.height as i64 / 2) as i32,
+            -(size.depth as i64 / 2) as i32,
+        );
+        Self::from_size(min, size)
+    }
+
+    // `None` if the maximum corner doesn't fit in an i32
+    pub fn checked_from_size(min: Offset3d<i32>, size: Extent3d<u32>) -> Option<Self> {
+        let get_max = |min: i32, size: u32| i32::try_from(min as i64 + size as i64 - 1).ok();
+        let max = Offset3d::new(
+            get_max(min.x, size.width)?,
+            get_max(min.y, size.height)?,
+            get_max(min.z, size.depth)?,
+        );
+        size.is_valid().then_some(Self { min, max })
+    }
+
+    pub fn size(&self) -> Extent3d<u32> {
+        Extent3d::new(
+            (self.max.x - self.min.x + 1) as u32,
+            (self.max.y - self.min.y + 1) as u32,
+            (self.max.z - self.min.z + 1) as u32,
+        )
+    }
+
+    pub fn volume(&self) -> usize {
+        let size = self.size();
+        size.width as usize * size.height as usize * size.depth as usize
+    }
+
+    pub fn contains(&self, position: Offset3d<i32>) -> bool {
+        position.x >= self.min.x
+            && position.y >= self.min.y
+            && position.z >= self.min.z
+            && position.x <= self.max.x
+            && position.y <= self.max.y
+            && position.z <= self.max.z
+    }
+
+    pub fn intersection(&self, other: &Self) -> Option<Self> {
+        let min = Offset3d::new(
+            self.min.x.max(other.min.x),
+            self.min.y.max(other.min.y),
+            self.min.z.max(other.min.z),
+        );
+        let max = Offset3d::new(
+            self.max.x.min(other.max.x),
+            self.max.y.min(other.max.y),
+            self.max.z.min(other.max.z),
+        );
+        (min.x <= max.x && min.y <= max.y && min.z <= max.z).then_some(Self { min, max })
+    }
+
+    // the smallest region containing both
+    pub fn union(&self, other: &Self) -> Self {
+        Self {
+            min: Offset3d::new(
+                self.min.x.min(other.min.x),
+                self.min.y.min(other.min.y),
+                self.min.z.min(other.min.z),
+            ),
+            max: Offset3d::new(
+                self.max.x.max(other.max.x),
+                self.max.y.max(other.max.y),
+                self.max.z.max(other.max.z),
+            ),
+        }
+    }
+
+    pub fn expand(&self, amount: i32) -> Self {
+        let amount = Offset3d::new(amount, amount, amount);
+        Self::new(self.min - amount, self.max + amount)
+    }
+
+    // x fastest, then y, then z, matching the world's memory layout
+    pub fn positions(&self) -> impl Iterator<Item = Offset3d<i32>> {
+        let Self { min, max } = *self;
+        (min.z..=max.z).flat_map(move |z| {
+            (min.y..=max.y).flat_map(move |y| (min.x..=max.x).map(move |x| Offset3d::new(x, y, z)))
+        })
+    }
+}
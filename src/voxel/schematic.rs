@@ -0,0 +1,329 @@
+use super::{
+    file::{self, FileError},
+    Region, Voxel, World, WorldError,
+};
+use crate::{Extent3d, Offset3d};
+use std::io::{Read, Write};
+
+/// A standalone box of voxels, positions are relative to its minimum corner.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Schematic {
+    size: Extent3d<u32>,
+    voxels: Vec<Voxel>,
+    states: Option<Vec<u32>>,
+}
+
+/// Clockwise rotation about the Y axis when looking down.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Rotation {
+    #[default]
+    None,
+    Clockwise90,
+    Clockwise180,
+    Clockwise270,
+}
+
+/// Mirroring is applied before rotating.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct PasteOptions {
+    pub rotation: Rotation,
+    pub mirror_x: bool,
+    pub mirror_z: bool,
+    pub skip_air: bool,
+}
+
+impl Schematic {
+    const FILE_MAGIC: [u8; 4] = *b"NDRS";
+    const FILE_VERSION: u32 = 1;
+
+    pub fn new(size: Extent3d<u32>) -> Self {
+        let volume = size.width as usize * size.height as usize * size.depth as usize;
+        Self {
+            size,
+            voxels: vec![Voxel::Void; volume],
+            states: None,
+        }
+    }
+
+    pub fn size(&self) -> Extent3d<u32> {
+        self.size
+    }
+
+    pub fn get_voxel(&self, position: Offset3d<i32>) -> Option<&Voxel> {
+        self.voxels.get(self.get_index(position)?)
+    }
+
+    pub fn set_voxel(
+        &mut self,
+        position: Offset3d<i32>,
+        voxel: Voxel,
+    ) -> Result<Voxel, WorldError> {
+        let Some(index) = self.get_index(position) else { return Err(WorldError::PositionInvalid(position)); };
+        Ok(std::mem::replace(&mut self.voxels[index], voxel))
+    }
+
+    // states are free-form data carried alongside the voxels, such as an imported block id
+    pub fn get_state(&self, position: Offset3d<i32>) -> Option<u32> {
+        let index = self.get_index(position)?;
+        self.states.as_ref().map(|states| states[index])
+    }
+
+    pub fn set_state(&mut self, position: Offset3d<i32>, state: u32) -> Result<(), WorldError> {
+        let Some(index) = self.get_index(position) else { return Err(WorldError::PositionInvalid(position)); };
+        let volume = self.voxels.len();
+        self.states.get_or_insert_with(|| vec![0; volume])[index] = state;
+        Ok(())
+    }
+
+    pub fn has_states(&self) -> bool {
+        self.states.is_some()
+    }
+
+    // the size once pasted with `rotation`
+    pub fn rotated_size(&self, rotation: Rotation) -> Extent3d<u32> {
+        match rotation {
+            Rotation::None | Rotation::Clockwise180 => self.size,
+            Rotation::Clockwise90 | Rotation::Clockwise270 => {
+                Extent3d::new(self.size.depth, self.size.height, self.size.width)
+            }
+        }
+    }
+
+    // layout: magic, version, size, state flag, run-length encoded voxels, states
+    pub fn save<W: Write>(&self, writer: &mut W) -> Result<(), FileError> {
+        writer.write_all(&Self::FILE_MAGIC)?;
+        file::write_u32(writer, Self::FILE_VERSION)?;
+        file::write_u32(writer, self.size.width)?;
+        file::write_u32(writer, self.size.height)?;
+        file::write_u32(writer, self.size.depth)?;
+        file::write_u8(writer, self.states.is_some() as u8)?;
+        file::write_voxels(writer, self.voxels.iter().copied())?;
+        if let Some(states) = self.states.as_ref() {
+            for state in states {
+                file::write_u32(writer, *state)?;
+            }
+        }
+        Ok(())
+    }
+
+    // tile indices are checked against the world when pasting
+    pub fn load<R: Read>(reader: &mut R) -> Result<Self, FileError> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if magic != Self::FILE_MAGIC {
+            return Err(FileError::MagicInvalid);
+        }
+        let version = file::read_u32(reader)?;
+        if version != Self::FILE_VERSION {
+            return Err(FileError::VersionUnsupported(version));
+        }
+        let size = Extent3d::new(
+            file::read_u32(reader)?,
+            file::read_u32(reader)?,
+            file::read_u32(reader)?,
+        );
+        let volume = file::checked_volume(size)?;
+        let has_states = match file::read_u8(reader)? {
+            0 => false,
+            1 => true,
+            _ => return Err(FileError::DataInvalid),
+        };
+        let voxels = file::read_voxels(reader, volume)?;
+        let mut schematic = Self::new(size);
+        schematic.voxels = voxels;
+        if has_states {
+            let states = (0..volume)
+                .map(|_| file::read_u32(reader))
+                .collect::<Result<_, _>>()?;
+            schematic.states = Some(states);
+        }
+        Ok(schematic)
+    }
+
+    #[inline]
+    fn get_index(&self, position: Offset3d<i32>) -> Option<usize> {
+        let Self { size, .. } = *self;
+        if position.x < 0
+            || position.y < 0
+            || position.z < 0
+            || position.x as u32 >= size.width
+            || position.y as u32 >= size.height
+            || position.z as u32 >= size.depth
+        {
+            return None;
+        }
+        let (x, y, z) = (
+            position.x as usize,
+            position.y as usize,
+            position.z as usize,
+        );
+        Some((z * size.height as usize + y) * size.width as usize + x)
+    }
+
+    // maps a position inside the pasted box back to the schematic
+    fn get_source_position(&self, position: Offset3d<i32>, options: PasteOptions) -> Offset3d<i32> {
+        let (width, depth) = (self.size.width as i32, self.size.depth as i32);
+        let (x, z) = match options.rotation {
+            Rotation::None => (position.x, position.z),
+            Rotation::Clockwise90 => (position.z, depth - 1 - position.x),
+            Rotation::Clockwise180 => (width - 1 - position.x, depth - 1 - position.z),
+            Rotation::Clockwise270 => (width - 1 - position.z, position.x),
+        };
+        let x = if options.mirror_x { width - 1 - x } else { x };
+        let z = if options.mirror_z { depth - 1 - z } else { z };
+        Offset3d::new(x, position.y, z)
+    }
+}
+
+impl World {
+    pub fn copy(&self, region: Region) -> Result<Schematic, WorldError> {
+        let bounds = self.bounds();
+        for corner in [region.min, region.max] {
+            if !bounds.contains(corner) {
+                return Err(WorldError::PositionInvalid(corner));
+            }
+        }
+        let mut schematic = Schematic::new(region.size());
+        schematic.voxels = region
+            .positions()
+            .map(|position| self.get_voxel(position).copied().unwrap_or_default())
+            .collect();
+        Ok(schematic)
+    }
+
+    /// Pastes `schematic` with its minimum corner at `origin` as one "paste" transaction and
+    /// returns the number of voxels changed. Parts outside the world are clipped.
+    pub fn paste(
+        &mut self,
+        schematic: &Schematic,
+        origin: Offset3d<i32>,
+        options: PasteOptions,
+    ) -> Result<usize, WorldError> {
+        let region = Region::from_size(origin, schematic.rotated_size(options.rotation));
+        self.edit_region(region, "paste", |position, _| {
+            let source = schematic.get_source_position(position - origin, options);
+            match schematic.get_voxel(source).copied()? {
+                Voxel::Void if options.skip_air => None,
+                voxel => Some(voxel),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voxel::{new_test_world, Fluid};
+
+    fn new_schematic() -> Schematic {
+        let mut schematic = Schematic::new(Extent3d::new(3, 2, 1));
+        schematic
+            .set_voxel(Offset3d::new(0, 0, 0), Voxel::Tile(1))
+            .unwrap();
+        schematic
+            .set_voxel(Offset3d::new(2, 1, 0), Voxel::Fluid(Fluid::new_source(2)))
+            .unwrap();
+        schematic
+    }
+
+    fn save(schematic: &Schematic) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        schematic.save(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut schematic = new_schematic();
+        assert_eq!(
+            Schematic::load(&mut save(&schematic).as_slice()).unwrap(),
+            schematic
+        );
+        schematic.set_state(Offset3d::new(1, 0, 0), 42).unwrap();
+        let loaded = Schematic::load(&mut save(&schematic).as_slice()).unwrap();
+        assert_eq!(loaded.get_state(Offset3d::new(1, 0, 0)), Some(42));
+        assert_eq!(loaded, schematic);
+    }
+
+    #[test]
+    fn corrupt_input() {
+        let bytes = save(&new_schematic());
+        for length in [0, 6, 20, bytes.len() - 1] {
+            let result = Schematic::load(&mut &bytes[..length]);
+            assert!(matches!(result, Err(FileError::Io(_))), "length {length}");
+        }
+
+        let mut bytes = save(&new_schematic());
+        bytes[3] = b'W';
+        assert!(matches!(
+            Schematic::load(&mut bytes.as_slice()),
+            Err(FileError::MagicInvalid)
+        ));
+
+        // a size whose volume overflows, then a state flag that isn't a bool
+        let mut bytes = save(&new_schematic());
+        bytes[8..20].copy_from_slice(&[0xff; 12]);
+        assert!(matches!(
+            Schematic::load(&mut bytes.as_slice()),
+            Err(FileError::DataInvalid)
+        ));
+        let mut bytes = save(&new_schematic());
+        bytes[20] = 2;
+        assert!(matches!(
+            Schematic::load(&mut bytes.as_slice()),
+            Err(FileError::DataInvalid)
+        ));
+    }
+
+    #[test]
+    fn out_of_bounds() {
+        let mut schematic = new_schematic();
+        let outside = Offset3d::new(3, 0, 0);
+        assert_eq!(schematic.get_voxel(outside), None);
+        assert!(matches!(
+            schematic.set_voxel(outside, Voxel::Tile(0)),
+            Err(WorldError::PositionInvalid(_))
+        ));
+        assert!(schematic.set_state(Offset3d::new(0, -1, 0), 1).is_err());
+
+        let world = new_test_world();
+        let region = Region::new(Offset3d::new(2, 0, 0), Offset3d::new(4, 0, 0));
+        assert!(matches!(
+            world.copy(region),
+            Err(WorldError::PositionInvalid(_))
+        ));
+    }
+
+    #[test]
+    fn copy_and_paste_rotated() {
+        let mut world = new_test_world();
+        let schematic = new_schematic();
+        let options = PasteOptions {
+            rotation: Rotation::Clockwise90,
+            ..PasteOptions::default()
+        };
+        let origin = Offset3d::new(0, 0, 0);
+        assert_eq!(world.paste(&schematic, origin, options).unwrap(), 2);
+        let rotated_size = schematic.rotated_size(options.rotation);
+        assert_eq!(rotated_size, Extent3d::new(1, 2, 3));
+
+        let copy = world.copy(Region::from_size(origin, rotated_size)).unwrap();
+        let solid = |schematic: &Schematic| {
+            schematic
+                .voxels
+                .iter()
+                .filter(|voxel| **voxel != Voxel::Void)
+                .count()
+        };
+        assert_eq!(solid(&copy), 2);
+
+        // clipped at the world's edge
+        let edge = Offset3d::new(3, 3, 3);
+        assert_eq!(
+            world
+                .paste(&schematic, edge, PasteOptions::default())
+                .unwrap(),
+            1
+        );
+    }
+}
@@ -0,0 +1,369 @@
+use super::{nbt::Tag, FileError, Fluid, Schematic, Voxel};
+use crate::{Extent3d, Offset3d};
+use flate2::read::GzDecoder;
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::Read,
+};
+
+/// Maps block-state names such as `minecraft:oak_log[axis=y]` to voxels. A full state name is
+/// looked up first, then the name without its properties.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BlockMapping {
+    voxels: HashMap<String, Voxel>,
+}
+
+/// The result of importing a Sponge schematic. Voxel states hold indices into `palette`.
+#[derive(Clone, Debug)]
+pub struct SpongeImport {
+    pub schematic: Schematic,
+    pub palette: Vec<String>,
+    pub unmapped: BTreeMap<String, usize>,
+}
+
+impl BlockMapping {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, name: &str, voxel: Voxel) {
+        self.voxels.insert(name.to_owned(), voxel);
+    }
+
+    pub fn get(&self, name: &str) -> Option<Voxel> {
+        let base_name = name
+            .split_once('[')
+            .map_or(name, |(base_name, _)| base_name);
+        self.voxels
+            .get(name)
+            .or_else(|| self.voxels.get(base_name))
+            .copied()
+    }
+
+    /// The tile count a world needs to hold every mapped voxel.
+    pub fn max_tiles(&self) -> u32 {
+        self.voxels
+            .values()
+            .filter_map(|voxel| match voxel {
+                Voxel::Void => None,
+                Voxel::Tile(tile_index) => Some(tile_index + 1),
+                Voxel::Fluid(fluid) => Some(fluid.tile_index + 1),
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    // one `<name> = void | tile <index> | fluid <index>` per line, `#` starts a comment
+    pub fn parse(text: &str) -> Result<Self, FileError> {
+        let mut mapping = Self::new();
+        for (line_index, line) in text.lines().enumerate() {
+            let line = line.split_once('#').map_or(line, |(line, _)| line).trim();
+            if line.is_empty() {
+                continue;
+            }
+            let invalid = || FileError::MappingInvalid(line_index + 1);
+            let (name, value) = line.split_once('=').ok_or_else(invalid)?;
+            let parse_index = |index: &str| index.parse().map_err(|_| invalid());
+            let voxel = match value.split_whitespace().collect::<Vec<_>>()[..] {
+                ["void"] => Voxel::Void,
+                ["tile", index] => Voxel::Tile(parse_index(index)?),
+                ["fluid", index] => Voxel::Fluid(Fluid::new_source(parse_index(index)?)),
+                _ => return Err(invalid()),
+            };
+            mapping.insert(name.trim(), voxel);
+        }
+        Ok(mapping)
+    }
+
+    pub fn load<R: Read>(reader: &mut R) -> Result<Self, FileError> {
+        let mut text = String::new();
+        reader.read_to_string(&mut text)?;
+        Self::parse(&text)
+    }
+}
+
+impl Schematic {
+    /// Reads a Sponge `.schem` file, versions 1 to 3, gzip compressed or not. Blocks without a
+    /// mapping become void and are counted in [`SpongeImport::unmapped`].
+    pub fn load_sponge<R: Read>(
+        reader: &mut R,
+        mapping: &BlockMapping,
+    ) -> Result<SpongeImport, FileError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        let (_, root) = if bytes.starts_with(&[0x1F, 0x8B]) {
+            Tag::read_root(&mut GzDecoder::new(bytes.as_slice()))?
+        } else {
+            Tag::read_root(&mut bytes.as_slice())?
+        };
+
+        // version 3 nests everything in a "Schematic" compound
+        let root = root.get("Schematic").unwrap_or(&root);
+        let get_integer = |name| root.get(name).and_then(Tag::as_integer);
+        let version = get_integer("Version").ok_or(FileError::DataInvalid)?;
+        let (palette, data) = match version {
+            1 | 2 => (root.get("Palette"), root.get("BlockData")),
+            3 => {
+                let blocks = root.get("Blocks").ok_or(FileError::DataInvalid)?;
+                (blocks.get("Palette"), blocks.get("Data"))
+            }
+            _ => return Err(FileError::VersionUnsupported(version as u32)),
+        };
+        let palette = palette
+            .and_then(Tag::as_compound)
+            .ok_or(FileError::DataInvalid)?;
+        let data = data.and_then(Tag::as_bytes).ok_or(FileError::DataInvalid)?;
+
+        // dimensions are unsigned shorts
+        let [width, height, length] = ["Width", "Height", "Length"]
+            .map(|name| get_integer(name).map_or(0, |value| value as u16 as u32));
+        let size = Extent3d::new(width, height, length);
+        if !size.is_valid() {
+            return Err(FileError::DataInvalid);
+        }
+        // every varint takes at least a byte, so smaller data can't cover the volume
+        let volume = (width as usize)
+            .checked_mul(height as usize)
+            .and_then(|area| area.checked_mul(length as usize))
+            .ok_or(FileError::DataInvalid)?;
+        if data.len() < volume {
+            return Err(FileError::DataInvalid);
+        }
+
+        let mut names = vec![None; palette.len()];
+        for (name, index) in palette {
+            let index = index.as_integer().ok_or(FileError::DataInvalid)?;
+            let Some(slot) = names.get_mut(index as usize) else { return Err(FileError::DataInvalid); };
+            *slot = Some(name.clone());
+        }
+        let palette: Vec<String> = names
+            .into_iter()
+            .collect::<Option<_>>()
+            .ok_or(FileError::DataInvalid)?;
+        let voxels: Vec<_> = palette.iter().map(|name| mapping.get(name)).collect();
+
+        // block indices are varints ordered x first, then z, then y
+        let mut schematic = Self::new(size);
+        let mut unmapped = BTreeMap::new();
+        let mut data = data.iter();
+        for y in 0..height as i32 {
+            for z in 0..length as i32 {
+                for x in 0..width as i32 {
+                    let index = read_varint(&mut data)? as usize;
+                    let voxel = match voxels.get(index) {
+                        Some(Some(voxel)) => *voxel,
+                        Some(None) => {
+                            *unmapped.entry(palette[index].clone()).or_insert(0) += 1;
+                            Voxel::Void
+                        }
+                        None => return Err(FileError::DataInvalid),
+                    };
+                    let position = Offset3d::new(x, y, z);
+                    schematic.set_voxel(position, voxel)?;
+                    schematic.set_state(position, index as u32)?;
+                }
+            }
+        }
+
+        Ok(SpongeImport {
+            schematic,
+            palette,
+            unmapped,
+        })
+    }
+}
+
+fn read_varint<'a>(bytes: &mut impl Iterator<Item = &'a u8>) -> Result<u32, FileError> {
+    let mut value = 0u32;
+    for shift in (0..32).step_by(7) {
+        let byte = *bytes.next().ok_or(FileError::DataInvalid)?;
+        value |= ((byte & 0x7F) as u32) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(FileError::DataInvalid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{write::GzEncoder, Compression};
+    use std::io::Write;
+
+    // just enough of an nbt writer to build schematics, everything is big-endian
+    fn write_name(bytes: &mut Vec<u8>, id: u8, name: &str) {
+        bytes.push(id);
+        bytes.extend((name.len() as u16).to_be_bytes());
+        bytes.extend(name.as_bytes());
+    }
+
+    fn write_short(bytes: &mut Vec<u8>, name: &str, value: i16) {
+        write_name(bytes, 2, name);
+        bytes.extend(value.to_be_bytes());
+    }
+
+    fn write_int(bytes: &mut Vec<u8>, name: &str, value: i32) {
+        write_name(bytes, 3, name);
+        bytes.extend(value.to_be_bytes());
+    }
+
+    fn write_byte_array(bytes: &mut Vec<u8>, name: &str, data: &[u8]) {
+        write_name(bytes, 7, name);
+        bytes.extend((data.len() as i32).to_be_bytes());
+        bytes.extend(data);
+    }
+
+    fn write_palette(bytes: &mut Vec<u8>, palette: &[(&str, i32)]) {
+        write_name(bytes, 10, "Palette");
+        for &(name, index) in palette {
+            write_int(bytes, name, index);
+        }
+        bytes.push(0);
+    }
+
+    fn encode(version: i32, size: [i16; 3], palette: &[(&str, i32)], data: &[u8]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        write_name(&mut bytes, 10, "");
+        if version == 3 {
+            write_name(&mut bytes, 10, "Schematic");
+        }
+        write_int(&mut bytes, "Version", version);
+        for (name, value) in ["Width", "Height", "Length"].into_iter().zip(size) {
+            write_short(&mut bytes, name, value);
+        }
+        if version == 3 {
+            write_name(&mut bytes, 10, "Blocks");
+            write_palette(&mut bytes, palette);
+            write_byte_array(&mut bytes, "Data", data);
+            bytes.extend([0, 0]);
+        } else {
+            write_palette(&mut bytes, palette);
+            write_byte_array(&mut bytes, "BlockData", data);
+        }
+        bytes.push(0);
+        bytes
+    }
+
+    fn new_mapping() -> BlockMapping {
+        BlockMapping::parse("minecraft:stone = tile 2\nminecraft:water = fluid 1 # comment\n")
+            .unwrap()
+    }
+
+    const PALETTE: [(&str, i32); 3] = [
+        ("minecraft:air", 0),
+        ("minecraft:stone", 1),
+        ("minecraft:water[level=0]", 2),
+    ];
+
+    #[test]
+    fn imports_blocks_in_order() {
+        // 2x2x1, x first, then z, then y
+        let bytes = encode(2, [2, 2, 1], &PALETTE, &[1, 0, 2, 0]);
+        let import = Schematic::load_sponge(&mut bytes.as_slice(), &new_mapping()).unwrap();
+        let schematic = &import.schematic;
+        assert_eq!(schematic.size(), Extent3d::new(2, 2, 1));
+        assert_eq!(
+            schematic.get_voxel(Offset3d::new(0, 0, 0)),
+            Some(&Voxel::Tile(2))
+        );
+        assert_eq!(
+            schematic.get_voxel(Offset3d::new(1, 0, 0)),
+            Some(&Voxel::Void)
+        );
+        assert_eq!(
+            schematic.get_voxel(Offset3d::new(0, 1, 0)),
+            Some(&Voxel::Fluid(Fluid::new_source(1)))
+        );
+        assert_eq!(schematic.get_state(Offset3d::new(0, 1, 0)), Some(2));
+        assert_eq!(import.palette[2], "minecraft:water[level=0]");
+        assert_eq!(import.unmapped.get("minecraft:air"), Some(&2));
+    }
+
+    #[test]
+    fn imports_compressed_version_3() {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder
+            .write_all(&encode(3, [1, 1, 2], &PALETTE, &[1, 1]))
+            .unwrap();
+        let bytes = encoder.finish().unwrap();
+        let import = Schematic::load_sponge(&mut bytes.as_slice(), &new_mapping()).unwrap();
+        assert_eq!(
+            import.schematic.get_voxel(Offset3d::new(0, 0, 1)),
+            Some(&Voxel::Tile(2))
+        );
+        assert!(import.unmapped.is_empty());
+    }
+
+    #[test]
+    fn rejects_corrupt_input() {
+        let mapping = new_mapping();
+        let load = |bytes: Vec<u8>| Schematic::load_sponge(&mut bytes.as_slice(), &mapping);
+
+        // data too short for the volume, or indexing past the palette
+        assert!(matches!(
+            load(encode(2, [2, 2, 1], &PALETTE, &[1, 0, 2])),
+            Err(FileError::DataInvalid)
+        ));
+        assert!(matches!(
+            load(encode(2, [1, 1, 1], &PALETTE, &[3])),
+            Err(FileError::DataInvalid)
+        ));
+        // an unterminated varint
+        assert!(matches!(
+            load(encode(2, [1, 1, 1], &PALETTE, &[0x80])),
+            Err(FileError::DataInvalid)
+        ));
+        // palettes with gaps, empty volumes and unknown versions
+        let gap = [("minecraft:air", 0), ("minecraft:stone", 2)];
+        assert!(matches!(
+            load(encode(2, [1, 1, 1], &gap, &[0])),
+            Err(FileError::DataInvalid)
+        ));
+        assert!(matches!(
+            load(encode(2, [0, 1, 1], &PALETTE, &[])),
+            Err(FileError::DataInvalid)
+        ));
+        assert!(matches!(
+            load(encode(4, [1, 1, 1], &PALETTE, &[0])),
+            Err(FileError::VersionUnsupported(4))
+        ));
+
+        let bytes = encode(2, [1, 1, 1], &PALETTE, &[0]);
+        assert!(matches!(load(bytes[..10].to_vec()), Err(FileError::Io(_))));
+        assert!(matches!(
+            load(bytes[..bytes.len() - 2].to_vec()),
+            Err(FileError::DataInvalid)
+        ));
+        assert!(load(vec![8, 0, 0]).is_err());
+    }
+
+    #[test]
+    fn varints_span_bytes() {
+        assert_eq!(read_varint(&mut [0x80, 0x01].iter()).unwrap(), 128);
+        assert_eq!(read_varint(&mut [0x7F].iter()).unwrap(), 127);
+        assert!(read_varint(&mut [0xFF; 6].iter()).is_err());
+    }
+
+    #[test]
+    fn mapping_falls_back_to_base_name() {
+        let mapping = new_mapping();
+        assert_eq!(
+            mapping.get("minecraft:stone[variant=smooth]"),
+            Some(Voxel::Tile(2))
+        );
+        assert_eq!(mapping.get("minecraft:dirt"), None);
+        assert!(matches!(
+            BlockMapping::parse("a = tile 1\nb = tile x\n"),
+            Err(FileError::MappingInvalid(2))
+        ));
+    }
+
+    #[test]
+    fn mapping_max_tiles() {
+        assert_eq!(new_mapping().max_tiles(), 3);
+        let mapping = BlockMapping::parse("a = void\nb = fluid 3\nc = tile 1\n").unwrap();
+        assert_eq!(mapping.max_tiles(), 4);
+        assert_eq!(BlockMapping::new().max_tiles(), 0);
+    }
+}
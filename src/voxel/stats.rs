@@ -0,0 +1,190 @@
+use super::{Faces, Region, Voxel, VoxelData, World};
+use crate::{graphics::mesh::Vertex, Offset3d};
+use std::mem;
+
+/// A snapshot of what a world holds, see [`World::stats`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct WorldStats {
+    pub void_count: usize,
+    /// Tile voxels per tile index.
+    pub tile_counts: Vec<usize>,
+    /// Fluid voxels per tile index.
+    pub fluid_counts: Vec<usize>,
+    pub face_count: usize,
+    /// What a full, non greedy mesh of the world would need.
+    pub vertex_count: usize,
+    pub index_count: usize,
+    /// Approximate bytes used on the CPU by voxels, textures, the history and the mesh.
+    pub memory_used: usize,
+}
+
+/// A voxel whose maintained faces differ from the ones computed from its neighbours.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FaceMismatch {
+    pub position: Offset3d<i32>,
+    pub expected: Faces,
+    pub actual: Faces,
+}
+
+impl WorldStats {
+    pub fn solid_count(&self) -> usize {
+        self.tile_counts.iter().sum()
+    }
+
+    pub fn fluid_count(&self) -> usize {
+        self.fluid_counts.iter().sum()
+    }
+}
+
+impl World {
+    pub fn stats(&self) -> WorldStats {
+        let mut stats = WorldStats {
+            tile_counts: vec![0; self.max_tiles as usize],
+            fluid_counts: vec![0; self.max_tiles as usize],
+            ..Default::default()
+        };
+        for voxel_data in &self.voxel_data {
+            match voxel_data.voxel {
+                Voxel::Void => stats.void_count += 1,
+                Voxel::Tile(tile_index) => stats.tile_counts[tile_index as usize] += 1,
+                Voxel::Fluid(fluid) => stats.fluid_counts[fluid.tile_index as usize] += 1,
+            }
+            stats.face_count += voxel_data.faces.iter().count();
+        }
+        stats.vertex_count = stats.face_count * 4;
+        stats.index_count = stats.face_count * 6;
+
+        let pixel_bytes: usize = self.tile_pixels.iter().flatten().map(Vec::len).sum();
+        let mesh_bytes = self.mesh.as_ref().map_or(0, |mesh| {
+            let index_count: usize = mesh
+                .submeshes
+                .iter()
+                .map(|submesh| submesh.indices.len())
+                .sum();
+            mesh.vertices.len() * mem::size_of::<Vertex>() + index_count * mem::size_of::<u32>()
+        });
+        stats.memory_used = self.voxel_data.capacity() * mem::size_of::<VoxelData>()
+            + pixel_bytes
+            + self.history.memory_used()
+            + mesh_bytes;
+        stats
+    }
+
+    /// Recomputes the faces of every voxel from scratch and returns those that differ from the
+    /// incrementally maintained ones, in index order.
+    pub fn validate(&self) -> Vec<FaceMismatch> {
+        (0..self.voxel_data.len())
+            .filter_map(|index| {
+                let position = unsafe { self.get_voxel_position_unchecked(index) };
+                let expected = self.compute_faces(position);
+                let actual = self.voxel_data[index].faces;
+                (expected != actual).then_some(FaceMismatch {
+                    position,
+                    expected,
+                    actual,
+                })
+            })
+            .collect()
+    }
+
+    /// Like [`World::validate`], but also overwrites the mismatching faces with the expected
+    /// ones.
+    pub fn repair(&mut self) -> Vec<FaceMismatch> {
+        let mismatches = self.validate();
+        self.suppress_events();
+        for mismatch in &mismatches {
+            let index = unsafe { self.get_voxel_index_unchecked(mismatch.position) };
+            self.voxel_data[index].faces = mismatch.expected;
+            self.publish_region_changed(Region::new(mismatch.position, mismatch.position));
+        }
+        self.resume_events();
+        mismatches
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voxel::{new_test_world, Boundary, Fluid};
+
+    #[test]
+    fn validate_reports_and_repair_fixes() {
+        let mut world = new_test_world();
+        let position = Offset3d::new(0, 0, 0);
+        world.set_voxel(position, Voxel::Tile(1)).unwrap();
+        assert!(world.validate().is_empty());
+
+        let index = world.get_voxel_index(position).unwrap();
+        let expected = world.voxel_data[index].faces;
+        world.voxel_data[index].faces = Faces::POS_X;
+        let mismatch = FaceMismatch {
+            position,
+            expected,
+            actual: Faces::POS_X,
+        };
+        assert_eq!(world.validate(), [mismatch]);
+
+        let events = world.subscribe();
+        assert_eq!(world.repair(), [mismatch]);
+        assert_eq!(world.get_faces(position), Some(expected));
+        assert!(world.validate().is_empty());
+        assert_eq!(events.drain().count(), 1);
+        assert!(world.repair().is_empty());
+    }
+
+    #[test]
+    fn faces_stay_valid_across_edits() {
+        let mut world = new_test_world();
+        let bounds = world.bounds();
+        world
+            .set_voxel(Offset3d::new(0, 0, 0), Voxel::Tile(0))
+            .unwrap();
+        world
+            .set_voxel(
+                Offset3d::new(1, 0, 0),
+                Voxel::Fluid(Fluid::new_flowing(1, 3)),
+            )
+            .unwrap();
+        assert!(world.validate().is_empty());
+
+        world
+            .fill(
+                Region::new(bounds.min, Offset3d::new(3, -2, 3)),
+                Voxel::Tile(1),
+            )
+            .unwrap();
+        world
+            .fill_sphere(Offset3d::new(0, 0, 0), 2, Voxel::Tile(2))
+            .unwrap();
+        world.hollow(bounds).unwrap();
+        world.walls(bounds, Voxel::Tile(0)).unwrap();
+        world.replace(bounds, Voxel::Tile(0), Voxel::Void).unwrap();
+        assert!(world.validate().is_empty());
+
+        world.resize(Region::new(
+            Offset3d::new(-2, -2, -2),
+            Offset3d::new(5, 1, 1),
+        ));
+        assert!(world.validate().is_empty());
+        world.expand(Region::new(
+            Offset3d::new(-6, 0, 0),
+            Offset3d::new(-6, 0, 0),
+        ));
+        assert!(world.validate().is_empty());
+
+        world.set_boundary(Boundary::Wrap);
+        let Region { min, max } = world.bounds();
+        world.set_voxel(min, Voxel::Tile(1)).unwrap();
+        world.set_voxel(max, Voxel::Tile(1)).unwrap();
+        world
+            .fill(
+                Region::new(min, Offset3d::new(min.x, max.y, max.z)),
+                Voxel::Tile(2),
+            )
+            .unwrap();
+        assert!(world.validate().is_empty());
+        world.set_voxel(max, Voxel::Void).unwrap();
+        world.set_boundary(Boundary::Solid);
+        assert!(world.validate().is_empty());
+    }
+}
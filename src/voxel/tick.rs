@@ -0,0 +1,388 @@
+use super::{fluid, Face, Region, Voxel, World, WorldError};
+use crate::{Extent3d, Offset3d, Random};
+use std::{collections::HashSet, mem, rc::Rc};
+
+/// Per-tile simulation hooks, registered with [`World::set_tile_behaviour`].
+pub trait TickBehaviour {
+    /// Called once a tick scheduled with [`World::schedule_tick`] is due.
+    fn scheduled_tick(
+        &self,
+        _world: &mut World,
+        _position: Offset3d<i32>,
+    ) -> Result<(), WorldError> {
+        Ok(())
+    }
+
+    /// Called when the voxel is picked as one of the random samples of its region.
+    fn random_tick(&self, _world: &mut World, _position: Offset3d<i32>) -> Result<(), WorldError> {
+        Ok(())
+    }
+
+    /// Called when the voxel at `neighbour` changed, `neighbour` equals `position` when the voxel
+    /// itself was changed or it was part of a bulk edit.
+    fn neighbour_changed(
+        &self,
+        _world: &mut World,
+        _position: Offset3d<i32>,
+        _neighbour: Offset3d<i32>,
+    ) -> Result<(), WorldError> {
+        Ok(())
+    }
+}
+
+/// Falls through void and fluids, like sand.
+#[derive(Clone, Copy, Debug)]
+pub struct Falling {
+    pub delay: u64,
+}
+
+impl TickBehaviour for Falling {
+    fn scheduled_tick(&self, world: &mut World, position: Offset3d<i32>) -> Result<(), WorldError> {
+        let below = position + Face::NegY.get_voxel_normal();
+        // swapped rather than overwritten, so fluids it falls into are displaced upwards
+        if let Some(&displaced @ (Voxel::Void | Voxel::Fluid(_))) = world.get_voxel(below) {
+            let voxel = world.set_voxel(position, displaced)?;
+            world.set_voxel(below, voxel)?;
+        }
+        Ok(())
+    }
+
+    fn neighbour_changed(
+        &self,
+        world: &mut World,
+        position: Offset3d<i32>,
+        _neighbour: Offset3d<i32>,
+    ) -> Result<(), WorldError> {
+        world.schedule_tick(position, self.delay)
+    }
+}
+
+/// Spreads onto nearby `target` tiles that are uncovered, like grass onto dirt.
+#[derive(Clone, Copy, Debug)]
+pub struct Spreading {
+    pub target: u32,
+}
+
+impl TickBehaviour for Spreading {
+    fn random_tick(&self, world: &mut World, position: Offset3d<i32>) -> Result<(), WorldError> {
+        let Some(&voxel) = world.get_voxel(position) else { return Ok(()); };
+        let offset = {
+            let random = world.random();
+            Offset3d::new(
+                random.range(3) as i32 - 1,
+                random.range(3) as i32 - 1,
+                random.range(3) as i32 - 1,
+            )
+        };
+        let other_position = position + offset;
+        let above = other_position + Face::PosY.get_voxel_normal();
+        if world.get_voxel(other_position) == Some(&Voxel::Tile(self.target))
+            && matches!(world.get_voxel(above), None | Some(Voxel::Void))
+        {
+            world.set_voxel(other_position, voxel)?;
+        }
+        Ok(())
+    }
+}
+
+/// Grows into the `next` tile with a one in `chance` probability per random tick, like crops.
+#[derive(Clone, Copy, Debug)]
+pub struct Growing {
+    pub next: u32,
+    pub chance: u32,
+}
+
+impl TickBehaviour for Growing {
+    fn random_tick(&self, world: &mut World, position: Offset3d<i32>) -> Result<(), WorldError> {
+        if world.random().range(self.chance) == 0 {
+            world.set_voxel(position, Voxel::Tile(self.next))?;
+        }
+        Ok(())
+    }
+}
+
+impl World {
+    const REGION_SIZE: u32 = 16;
+
+    pub fn tick_count(&self) -> u64 {
+        self.tick_count
+    }
+
+    pub fn set_seed(&mut self, seed: u64) {
+        self.random = Random::new(seed);
+    }
+
+    pub fn random(&mut self) -> &mut Random {
+        &mut self.random
+    }
+
+    pub fn set_random_tick_count(&mut self, count: u32) {
+        self.random_tick_count = count;
+    }
+
+    pub fn set_tile_behaviour<B: TickBehaviour + 'static>(
+        &mut self,
+        tile_index: u32,
+        behaviour: B,
+    ) -> Result<(), WorldError> {
+        let Some(tile_behaviour) = self.tile_behaviours.get_mut(tile_index as usize) else { return Err(WorldError::TileIndexInvalid(tile_index)); };
+        *tile_behaviour = Some(Rc::new(behaviour));
+        Ok(())
+    }
+
+    pub fn schedule_tick(&mut self, position: Offset3d<i32>, delay: u64) -> Result<(), WorldError> {
+        if self.get_voxel_index(position).is_none() {
+            return Err(WorldError::PositionInvalid(position));
+        }
+        self.scheduled_ticks
+            .entry(self.tick_count + delay.max(1))
+            .or_default()
+            .push(position);
+        Ok(())
+    }
+
+    /// Advances the simulation by one tick and returns the number of voxels changed. Changes made
+    /// by the simulation are not recorded in the edit history, and undo leaves them in place.
+    pub fn tick(&mut self) -> Result<usize, WorldError> {
+        let recording = self.history.set_recording(false);
+        let result = self.run_tick();
+        self.history.set_recording(recording);
+        result
+    }
+
+    fn run_tick(&mut self) -> Result<usize, WorldError> {
+        self.tick_count += 1;
+        let revision = self.revision;
+
+        // scheduled ticks, anything scheduled while running them is due next tick at the earliest
+        let pending = self.scheduled_ticks.split_off(&(self.tick_count + 1));
+        let due = mem::replace(&mut self.scheduled_ticks, pending);
+        let mut visited = HashSet::new();
+        for position in due.into_values().flatten() {
+            if !visited.insert(position) {
+                continue;
+            }
+            match self.get_voxel(position).copied() {
+                Some(Voxel::Fluid(_)) => self.update_fluid(position)?,
+                Some(Voxel::Tile(tile_index)) => {
+                    if let Some(behaviour) = self.get_tile_behaviour(tile_index) {
+                        behaviour.scheduled_tick(self, position)?;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        // random ticks
+        self.random_tick()?;
+
+        // neighbour notifications, including the ones fired above
+        self.queued_updates.clear();
+        for (position, neighbour) in mem::take(&mut self.neighbour_updates) {
+            match self.get_voxel(position).copied() {
+                Some(Voxel::Fluid(_)) => self.schedule_tick(position, fluid::FLOW_DELAY)?,
+                Some(Voxel::Tile(tile_index)) => {
+                    if let Some(behaviour) = self.get_tile_behaviour(tile_index) {
+                        behaviour.neighbour_changed(self, position, neighbour)?;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok((self.revision - revision) as usize)
+    }
+
+    // queue notifications for the changed voxel and its neighbours, if anything listens
+    pub(super) fn notify_neighbours(&mut self, position: Offset3d<i32>) {
+        let neighbours = (0..Face::CUBE_FACE_COUNT)
+            .map(|face_index| position + Face::from_index(face_index).get_voxel_normal());
+        for other_position in Some(position).into_iter().chain(neighbours) {
+            self.queue_update(other_position, position);
+        }
+    }
+
+    // queue a notification for every listening voxel in the region, used by bulk edits
+    pub(super) fn notify_region(&mut self, region: Region) {
+        let Some(region) = region.intersection(&self.bounds()) else { return; };
+        for position in region.positions() {
+            self.queue_update(position, position);
+        }
+    }
+
+    // repeated edits between ticks queue each notification once, so the queue is bounded by the
+    // number of listening voxels
+    #[inline]
+    fn queue_update(&mut self, position: Offset3d<i32>, neighbour: Offset3d<i32>) {
+        if self.is_listening(position) && self.queued_updates.insert((position, neighbour)) {
+            self.neighbour_updates.push((position, neighbour));
+        }
+    }
+
+    // sample `random_tick_count` voxels in every region
+    fn random_tick(&mut self) -> Result<(), WorldError> {
+        let regions = Extent3d::new(
+            self.size.width.div_ceil(Self::REGION_SIZE),
+            self.size.height.div_ceil(Self::REGION_SIZE),
+            self.size.depth.div_ceil(Self::REGION_SIZE),
+        );
+        for region_z in 0..regions.depth {
+            for region_y in 0..regions.height {
+                for region_x in 0..regions.width {
+                    for _ in 0..self.random_tick_count {
+                        let x = region_x * Self::REGION_SIZE + self.random.range(Self::REGION_SIZE);
+                        let y = region_y * Self::REGION_SIZE + self.random.range(Self::REGION_SIZE);
+                        let z = region_z * Self::REGION_SIZE + self.random.range(Self::REGION_SIZE);
+                        let position =
+                            self.origin_offset + Offset3d::new(x as i32, y as i32, z as i32);
+                        let Some(&Voxel::Tile(tile_index)) = self.get_voxel(position) else { continue; };
+                        if let Some(behaviour) = self.get_tile_behaviour(tile_index) {
+                            behaviour.random_tick(self, position)?;
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn is_listening(&self, position: Offset3d<i32>) -> bool {
+        match self.get_voxel(position) {
+            Some(Voxel::Fluid(_)) => true,
+            Some(&Voxel::Tile(tile_index)) => self.get_tile_behaviour(tile_index).is_some(),
+            _ => false,
+        }
+    }
+
+    #[inline]
+    fn get_tile_behaviour(&self, tile_index: u32) -> Option<Rc<dyn TickBehaviour>> {
+        self.tile_behaviours.get(tile_index as usize)?.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voxel::{new_test_world, Fluid};
+    use std::cell::RefCell;
+
+    // records every call it receives along with the tick it arrived on
+    #[derive(Default)]
+    struct Recorder {
+        scheduled: RefCell<Vec<u64>>,
+        neighbours: RefCell<Vec<(Offset3d<i32>, Offset3d<i32>)>>,
+    }
+
+    impl TickBehaviour for Rc<Recorder> {
+        fn scheduled_tick(
+            &self,
+            world: &mut World,
+            _position: Offset3d<i32>,
+        ) -> Result<(), WorldError> {
+            self.scheduled.borrow_mut().push(world.tick_count());
+            Ok(())
+        }
+
+        fn neighbour_changed(
+            &self,
+            _world: &mut World,
+            position: Offset3d<i32>,
+            neighbour: Offset3d<i32>,
+        ) -> Result<(), WorldError> {
+            self.neighbours.borrow_mut().push((position, neighbour));
+            Ok(())
+        }
+    }
+
+    fn new_spreading_world(seed: u64) -> World {
+        let mut world = new_test_world();
+        world.set_seed(seed);
+        world.set_random_tick_count(4096);
+        world
+            .set_tile_behaviour(1, Spreading { target: 0 })
+            .unwrap();
+        let Region { min, max } = world.bounds();
+        let ground = Region::new(min, Offset3d::new(max.x, -1, max.z));
+        world.fill(ground, Voxel::Tile(0)).unwrap();
+        world
+            .set_voxel(Offset3d::new(0, -1, 0), Voxel::Tile(1))
+            .unwrap();
+        world
+    }
+
+    #[test]
+    fn same_seed_is_deterministic() {
+        let mut first = new_spreading_world(7);
+        let mut second = new_spreading_world(7);
+        for _ in 0..50 {
+            assert_eq!(first.tick().unwrap(), second.tick().unwrap());
+        }
+
+        let bounds = first.bounds();
+        assert!(first.voxels(bounds).eq(second.voxels(bounds)));
+        let spread = first
+            .voxels(bounds)
+            .filter(|(_, voxel)| **voxel == Voxel::Tile(1))
+            .count();
+        assert!(spread > 1);
+    }
+
+    #[test]
+    fn scheduled_tick_fires_when_due() {
+        let mut world = new_test_world();
+        let recorder = Rc::new(Recorder::default());
+        world.set_tile_behaviour(1, recorder.clone()).unwrap();
+        let position = Offset3d::new(0, 0, 0);
+        world.set_voxel(position, Voxel::Tile(1)).unwrap();
+
+        world.tick().unwrap();
+        world.schedule_tick(position, 3).unwrap();
+        for _ in 0..6 {
+            world.tick().unwrap();
+        }
+        assert_eq!(*recorder.scheduled.borrow(), [4]);
+        assert!(world.schedule_tick(Offset3d::new(100, 0, 0), 1).is_err());
+    }
+
+    #[test]
+    fn neighbour_updates_are_delivered_once() {
+        let mut world = new_test_world();
+        let recorder = Rc::new(Recorder::default());
+        world.set_tile_behaviour(1, recorder.clone()).unwrap();
+        let (position, neighbour) = (Offset3d::new(0, 0, 0), Offset3d::new(1, 0, 0));
+        world.set_voxel(position, Voxel::Tile(1)).unwrap();
+        for voxel in [Voxel::Tile(0), Voxel::Void, Voxel::Tile(0)] {
+            world.set_voxel(neighbour, voxel).unwrap();
+        }
+
+        world.tick().unwrap();
+        assert_eq!(
+            *recorder.neighbours.borrow(),
+            [(position, position), (position, neighbour)]
+        );
+
+        world.tick().unwrap();
+        assert_eq!(recorder.neighbours.borrow().len(), 2);
+    }
+
+    #[test]
+    fn falling_tile_swaps_with_fluid() {
+        let mut world = new_test_world();
+        world.set_tile_behaviour(2, Falling { delay: 1 }).unwrap();
+        let Region { min, max } = world.bounds();
+        let floor = Region::new(min, Offset3d::new(max.x, min.y, max.z));
+        world.fill(floor, Voxel::Tile(0)).unwrap();
+        let (lower, upper) = (Offset3d::new(0, -3, 0), Offset3d::new(0, -2, 0));
+        world
+            .set_voxel(lower, Voxel::Fluid(Fluid::new_source(1)))
+            .unwrap();
+        world.set_voxel(upper, Voxel::Tile(2)).unwrap();
+        for _ in 0..4 {
+            world.tick().unwrap();
+        }
+
+        assert_eq!(world.get_voxel(lower), Some(&Voxel::Tile(2)));
+        assert!(matches!(world.get_voxel(upper), Some(Voxel::Fluid(fluid)) if fluid.source));
+    }
+}